@@ -0,0 +1,244 @@
+use crate::util::integrator::IntegratorKind;
+use crate::util::output::OutputFormat;
+use crate::util::sampler::SamplerKind;
+
+pub const USAGE: &str = "\
+Usage: rs-raycast [OPTIONS] [SCENE]
+
+Renders SCENE (default: scenes/default.scene) to an image.
+
+Options:
+  -o, --output <FILE>      where to write the image [default: output.png]
+  -f, --format <FORMAT>    png, jpg, ppm, exr or hdr [default: from the output
+                           extension]. exr and hdr keep values above 1
+  -W, --width <PIXELS>     image width, overrides the scene
+  -H, --height <PIXELS>    image height, overrides the scene. if only the
+                           width is given, the scene's aspect ratio is kept
+  -s, --samples <N>        samples per pixel, overrides the scene
+  -d, --max-depth <N>      maximum bounces per ray, overrides the scene
+  -j, --threads <N>        render threads [default: every core]
+  -i, --integrator <NAME>  path, direct, ao, normals, uv, depth or material
+                           [default: path]. everything but path is for
+                           checking a scene quickly
+      --sampler <NAME>     independent, stratified, halton or sobol
+                           [default: sobol]
+      --seed <N>           random seed. the same seed gives the same image,
+                           on any number of threads [default: random]
+  -p, --progressive <N>    render in passes of N samples per pixel, writing
+                           the image as it goes. ctrl-c stops early and
+                           keeps what's done
+      --write-every <SECS> time between writes in a progressive render
+                           [default: 10]
+      --time-limit <SECS>  stop a progressive render after this long
+      --missing-textures   render images that can't be loaded magenta, with a
+                           warning, instead of stopping
+      --no-bvh             test every shape for every ray (debugging)
+  -h, --help               print this help
+";
+
+// everything the user can set from the command line. None means "use
+// whatever the scene file says".
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub scene: String,
+    pub output: String,
+    pub format: Option<OutputFormat>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub samples: Option<i32>,
+    pub max_depth: Option<i32>,
+    pub threads: Option<usize>,
+    pub integrator: Option<IntegratorKind>,
+    pub sampler: Option<SamplerKind>,
+    pub seed: Option<u64>,
+    pub pass_samples: Option<i32>,
+    pub write_every: Option<f32>,
+    pub time_limit: Option<f32>,
+    pub missing_textures: bool,
+    pub no_bvh: bool,
+}
+
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene: "scenes/default.scene".to_owned(),
+            output: "output.png".to_owned(),
+            format: None,
+            width: None,
+            height: None,
+            samples: None,
+            max_depth: None,
+            threads: None,
+            integrator: None,
+            sampler: None,
+            seed: None,
+            pass_samples: None,
+            write_every: None,
+            time_limit: None,
+            missing_textures: false,
+            no_bvh: false,
+        }
+    }
+}
+
+fn positive(flag: &str, value: &str) -> Result<i32, String> {
+    match value.parse::<i32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} expects a whole number above 0, got '{}'", flag, value)),
+    }
+}
+
+fn seconds(flag: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("{} expects a number of seconds, got '{}'", flag, value)),
+    }
+}
+
+// parses everything after the program name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut scene: Option<String> = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // --flag=value is the same as --flag value
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_owned(), Some(v.to_owned())),
+            _ => (arg.clone(), None),
+        };
+
+        let mut value = || -> Result<String, String> {
+            inline.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => options.output = value()?,
+            "-f" | "--format" => {
+                let name = value()?;
+                options.format = Some(OutputFormat::from_name(&name)
+                    .ok_or_else(|| format!("unknown format '{}' (expected one of {})", name, OutputFormat::NAMES))?);
+            },
+            "-W" | "--width" => options.width = Some(positive(&flag, &value()?)?),
+            "-H" | "--height" => options.height = Some(positive(&flag, &value()?)?),
+            "-s" | "--samples" => options.samples = Some(positive(&flag, &value()?)?),
+            "-d" | "--max-depth" => options.max_depth = Some(positive(&flag, &value()?)?),
+            "-j" | "--threads" => options.threads = Some(positive(&flag, &value()?)? as usize),
+            "-i" | "--integrator" => {
+                let name = value()?;
+                options.integrator = Some(IntegratorKind::from_name(&name)
+                    .ok_or_else(|| format!("unknown integrator '{}' (expected one of {})", name, IntegratorKind::NAMES))?);
+            },
+            "--sampler" => {
+                let name = value()?;
+                options.sampler = Some(SamplerKind::from_name(&name)
+                    .ok_or_else(|| format!("unknown sampler '{}' (expected one of {})", name, SamplerKind::NAMES))?);
+            },
+            "--seed" => {
+                let v = value()?;
+                options.seed = Some(v.parse().map_err(|_| format!("--seed expects a whole number, got '{}'", v))?);
+            },
+            "-p" | "--progressive" => options.pass_samples = Some(positive(&flag, &value()?)?),
+            "--write-every" => options.write_every = Some(seconds(&flag, &value()?)?),
+            "--time-limit" => options.time_limit = Some(seconds(&flag, &value()?)?),
+            "--missing-textures" => options.missing_textures = true,
+            "--no-bvh" => options.no_bvh = true,
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option '{}'", flag));
+            },
+            _ => {
+                if scene.is_some() {
+                    return Err(format!("only one scene can be rendered at a time, got '{}' too", arg));
+                }
+                scene = Some(arg);
+            }
+        }
+    }
+
+    if let Some(scene) = scene {
+        options.scene = scene;
+    }
+
+    // the output name has to make sense for the format.
+    if options.format.is_none() && OutputFormat::from_path(&options.output).is_none() {
+        return Err(format!("can't tell the format of '{}' from its extension, pass --format", options.output));
+    }
+
+    Ok(Command::Render(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Result<Options, String> {
+        match parse_args(args.iter().map(|a| a.to_string()))? {
+            Command::Render(options) => Ok(options),
+            Command::Help => Err("help".to_owned()),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let o = options(&[]).unwrap();
+
+        assert_eq!(o.scene, "scenes/default.scene");
+        assert_eq!(o.output, "output.png");
+        assert_eq!(o.samples, None);
+        assert!(!o.no_bvh);
+    }
+
+    #[test]
+    fn flags_take_values_either_way() {
+        let o = options(&["-s", "16", "--max-depth=4", "--output=out.exr", "-j", "2", "my.scene"]).unwrap();
+
+        assert_eq!(o.samples, Some(16));
+        assert_eq!(o.max_depth, Some(4));
+        assert_eq!(o.output, "out.exr");
+        assert_eq!(o.threads, Some(2));
+        assert_eq!(o.scene, "my.scene");
+    }
+
+    #[test]
+    fn names_are_looked_up() {
+        let o = options(&["--integrator", "normals", "--sampler=halton", "--seed", "42"]).unwrap();
+
+        assert_eq!(o.integrator, IntegratorKind::from_name("normals"));
+        assert_eq!(o.sampler, SamplerKind::from_name("halton"));
+        assert_eq!(o.seed, Some(42));
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        assert_eq!(options(&["-s", "0"]).unwrap_err(), "-s expects a whole number above 0, got '0'");
+        assert_eq!(options(&["--width=-5"]).unwrap_err(), "--width expects a whole number above 0, got '-5'");
+        assert_eq!(options(&["--time-limit", "soon"]).unwrap_err(), "--time-limit expects a number of seconds, got 'soon'");
+        assert_eq!(options(&["--seed", "x"]).unwrap_err(), "--seed expects a whole number, got 'x'");
+        assert!(options(&["--integrator", "magic"]).unwrap_err().starts_with("unknown integrator 'magic'"));
+        assert_eq!(options(&["--samples"]).unwrap_err(), "--samples needs a value");
+        assert_eq!(options(&["--fast"]).unwrap_err(), "unknown option '--fast'");
+        assert_eq!(options(&["a.scene", "b.scene"]).unwrap_err(), "only one scene can be rendered at a time, got 'b.scene' too");
+    }
+
+    #[test]
+    fn format_comes_from_the_flag_or_the_extension() {
+        assert_eq!(options(&["-o", "a.exr"]).unwrap().format, None);
+        assert_eq!(options(&["-o", "a.img", "-f", "HDR"]).unwrap().format, Some(OutputFormat::Hdr));
+        assert_eq!(options(&["-o", "a.img"]).unwrap_err(), "can't tell the format of 'a.img' from its extension, pass --format");
+        assert!(options(&["-f", "gif"]).unwrap_err().starts_with("unknown format 'gif'"));
+        assert_eq!(OutputFormat::from_path("render.JPEG"), Some(OutputFormat::Jpeg));
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(options(&["-s", "4", "--help"]).unwrap_err(), "help");
+    }
+}
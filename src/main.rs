@@ -1,109 +1,80 @@
 pub mod util;
 pub mod math;
 pub mod shapes;
+pub mod scene;
+pub mod cli;
 
-use crate::util::image::{Image, DrawHeader};
+use crate::util::image::{DrawHeader, Progressive};
+use crate::util::output::OutputFormat;
+use crate::util::texture::TextureCache;
 use crate::math::vector::*;
-use crate::util::camera::Camera;
-use crate::util::hittable::*;
-use crate::util::material::*;
-use crate::shapes::sphere::*;
-use crate::shapes::shape::*;
-use crate::util::texture::Texture;
+use crate::scene::Scene;
+use crate::cli::*;
 
-use std::time::Instant;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
-fn main() -> std::io::Result<()> {
+use signal_hook::consts::SIGINT;
+
+fn render(options: &Options) -> std::io::Result<()> {
     // calculate program run-time.
     let now = Instant::now();
 
-    // image settings
-    let aspect_ratio: f32 = 16.0 / 10.0;
-    let width: i32 = 1920;
-    let height: i32 = (width as f32 / aspect_ratio) as i32;
-    let fov: f32 = 80.0;
-
-    let samples: i32 = 10; 
-
-    let image = Image::new(width, height, fov, samples);
-    let camera: Camera = Camera::new(
-        Point::new(0.0, 1.0, 0.0), // up
-        Point::new(0.0, 0.0, 0.1), // at 
-        Point::new(0.0, 0.0, 0.0), // to
-        image
-    );
-
-    // Materials
-    // let mat_ground = Material::Lambertian(
-    //     Texture::Solid(Color::new(0.3, 0.8, 0.2))
-    // );
-
-    let red = Material::Lambertian(
-        Texture::Solid(Color::new(1.0, 0.0, 0.0))
-    );
-
-    // let checker = Material::Lambertian(
-    //     Texture::from_img("assets/earthmap.jpg".to_owned())
-    // );
-
-    // 2 of the same material, because shapes take ownership of materials. 
-    let metal_clear = Material::Metal(Color::new(0.8, 0.8, 0.8));
-    let metal_clear2 = Material::Metal(Color::new(0.8, 0.8, 0.8));
-    let metal_clear3 = Material::Metal(Color::new(0.8, 0.8, 0.8));
-
-    // all render shapes
-    let mut world = World::new();
-
-    // For earth uv render.
-    // world.insert(Shape::sphere(Sphere::new_pos_t(Point::new(0.0, 0.0, -0.1), checker, 0.4)));
-    // world.insert(Shape::sphere(Sphere::new_pos_t(Point::new(-0.2, 0.0, 1.0), metal_clear, 0.45)));
-    // world.insert(
-    //     Shape::Sphere(
-    //         Sphere::new_pos_t(Point::new(-0.2, 0.0, 1.0), metal_clear, 0.45)
-    //     )
-    // );
-
-    // metal sphere behind the camera
-    world.insert(
-        Shape::sphere(
-            Sphere::new_pos_t(
-                Point::new(-0.2, 0.0, 1.0), 
-                metal_clear, 
-                0.4)));
-
-    // matte sphere
-    world.insert(
-        Shape::Sphere(
-            Sphere::new_pos_t(Point::new(0.0, -0.5, 0.0), red, 0.2)   
-        )
-    );
-
-    // metal sphere in front of camera.
-    world.insert(
-        Shape::Sphere(
-            Sphere::new_pos_t(Point::new(0.0, 0.0, -1.0), metal_clear2, 0.45)
-        )
-    );
-
-    
-
-    // GROUND 
-    world.insert(
-        Shape::sphere(
-            Sphere::new_pos_t(
-                Point::new(0.0,-101.0, -1.0), metal_clear3, 
-                100.0)));
-
-        // TODO Align planes to axes, or figure out how to angle a plane 
+    // image settings, camera and shapes all come from the scene file,
+    // command line options win over the scene.
+    let mut scene = Scene::load(&options.scene, TextureCache::new(options.missing_textures))?;
+
+    if options.width.is_some() || options.height.is_some() {
+        let aspect_ratio: f32 = scene.image.width as f32 / scene.image.height as f32;
+
+        let width = options.width
+            .unwrap_or_else(|| (options.height.unwrap() as f32 * aspect_ratio) as i32);
+        let height = options.height
+            .unwrap_or_else(|| (width as f32 / aspect_ratio) as i32);
+
+        scene.resize(width.max(1), height.max(1));
+    }
+
+    if let Some(samples) = options.samples { scene.image.samples_per_pixel = samples; }
+    if let Some(max_depth) = options.max_depth { scene.image.max_depth = max_depth; }
+    if let Some(threads) = options.threads { scene.image.threads = threads; }
+    if let Some(integrator) = options.integrator { scene.image.integrator = integrator; }
+    if let Some(sampler) = options.sampler { scene.image.sampler = sampler; }
+    if options.seed.is_some() { scene.image.seed = options.seed; }
+    scene.world.use_bvh = !options.no_bvh;
+
+    // any of the progressive options turns it on.
+    if options.pass_samples.is_some() || options.write_every.is_some() || options.time_limit.is_some() {
+        let progressive = scene.image.progressive.get_or_insert_with(Progressive::default);
+
+        if let Some(samples) = options.pass_samples { progressive.pass_samples = samples; }
+        if let Some(seconds) = options.write_every { progressive.write_interval = Duration::from_secs_f32(seconds); }
+        if let Some(seconds) = options.time_limit { progressive.time_limit = Some(Duration::from_secs_f32(seconds)); }
+    }
+
+    // the first ctrl-c ends a progressive render after the tiles in flight
+    // and writes what's there, a second one quits straight away.
+    let stop = Arc::new(AtomicBool::new(false));
+    if scene.image.progressive.is_some() {
+        signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&stop))?;
+        signal_hook::flag::register(SIGINT, Arc::clone(&stop))?;
+    }
 
     // render
     let header: DrawHeader = DrawHeader {
-        output_file: "output.ppm",
-        camera: &camera,
-        world: &world,
+        output_file: &options.output,
+        // parse_args already checked the extension if no format was given.
+        format: options.format
+            .or_else(|| OutputFormat::from_path(&options.output))
+            .unwrap_or(OutputFormat::Png),
+        camera: &scene.camera,
+        world: &scene.world,
+        stop: &stop,
     };
 
-    image.draw(&header)?; 
+    scene.image.draw(&header)?; 
 
     // record how long program took.
     let execution_time = now.elapsed();
@@ -111,3 +82,25 @@ fn main() -> std::io::Result<()> {
 
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        Err(message) => {
+            eprintln!("error: {}\n\nRun with --help to see the options.", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match render(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
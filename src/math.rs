@@ -1,11 +1,12 @@
 pub mod vector;
 pub mod ray;
+pub mod aabb;
+pub mod matrix;
+pub mod sampling;
+pub mod rng;
 
 use crate::math::vector::*;
 
-use rand::prelude::*;
-
-
 // I like degrees, fight me.
 pub fn deg_to_rad(degree: f32) -> f32 {
     use std::f32::consts::PI;
@@ -17,13 +18,6 @@ pub fn deg_to_rad(degree: f32) -> f32 {
     result
 }
 
-pub fn random_f32(min: f32, max: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    let y: f32 = rng.gen();
-
-    (max - min) * y + min 
-}
-
 pub fn clamp(x: f32, min: f32, max: f32) -> f32 {
     if x < min { return min; }
     if x > max { return max; }
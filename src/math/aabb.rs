@@ -0,0 +1,98 @@
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+
+// axis-aligned bounding box, used by the bvh to skip whole groups of shapes.
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    pub fn new(min: Point, max: Point) -> Self {
+        Aabb { min, max }
+    }
+
+    // an "inside out" box, so the first union always wins.
+    pub fn empty() -> Self {
+        Aabb {
+            min: Point::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Self {
+        Aabb {
+            min: Point::new(
+                self.min.a.min(other.min.a),
+                self.min.b.min(other.min.b),
+                self.min.c.min(other.min.c)
+            ),
+            max: Point::new(
+                self.max.a.max(other.max.a),
+                self.max.b.max(other.max.b),
+                self.max.c.max(other.max.c)
+            ),
+        }
+    }
+
+    pub fn grow(self, p: Point) -> Self {
+        self.union(Aabb::new(p, p))
+    }
+
+    pub fn centroid(&self) -> Point {
+        (self.min + self.max).scalar_mul(0.5)
+    }
+
+    pub fn extent(&self) -> Point {
+        self.max + self.min.scalar_mul(-1.0)
+    }
+
+    // index of the longest axis (0 = a, 1 = b, 2 = c).
+    pub fn longest_axis(&self) -> usize {
+        let e = self.extent();
+
+        if e.a > e.b && e.a > e.c { 0 }
+        else if e.b > e.c { 1 }
+        else { 2 }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let e = self.extent();
+        if e.a < 0.0 || e.b < 0.0 || e.c < 0.0 {
+            return 0.0;
+        }
+
+        2.0 * (e.a * e.b + e.b * e.c + e.c * e.a)
+    }
+
+    // slab test. returns the distance the ray enters the box at, if it
+    // enters before t_max. inv_dir is 1 / ray.direction, computed once per ray.
+    pub fn hit(&self, ray: &Ray, inv_dir: &Point, t_max: f32) -> Option<f32> {
+        let slab = |min: f32, max: f32, origin: f32, inv: f32| -> (f32, f32) {
+            let t0 = (min - origin) * inv;
+            let t1 = (max - origin) * inv;
+
+            if inv < 0.0 { (t1, t0) } else { (t0, t1) }
+        };
+
+        let (ax0, ax1) = slab(self.min.a, self.max.a, ray.origin.a, inv_dir.a);
+        let (bx0, bx1) = slab(self.min.b, self.max.b, ray.origin.b, inv_dir.b);
+        let (cx0, cx1) = slab(self.min.c, self.max.c, ray.origin.c, inv_dir.c);
+
+        // f32::max/min drop NaNs, which show up for axis-parallel rays
+        // grazing a slab (0 * inf).
+        let t_enter = ax0.max(bx0).max(cx0).max(0.0);
+        let t_exit = ax1.min(bx1).min(cx1).min(t_max);
+
+        if t_enter <= t_exit { Some(t_enter) } else { None }
+    }
+}
+
+pub fn axis(p: &Point, axis: usize) -> f32 {
+    match axis {
+        0 => p.a,
+        1 => p.b,
+        _ => p.c,
+    }
+}
@@ -0,0 +1,217 @@
+use std::ops::Mul;
+
+use crate::math::vector::*;
+
+// Row-major 4x4 affine transform. points are columns, so a * b applies b
+// first, then a.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Matrix4::identity()
+    }
+}
+
+impl Matrix4 {
+    pub fn identity() -> Self {
+        Matrix4 { m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ] }
+    }
+
+    pub fn translate(offset: Point) -> Self {
+        let mut t = Matrix4::identity();
+        t.m[0][3] = offset.a;
+        t.m[1][3] = offset.b;
+        t.m[2][3] = offset.c;
+        t
+    }
+
+    pub fn scale(factor: Point) -> Self {
+        let mut s = Matrix4::identity();
+        s.m[0][0] = factor.a;
+        s.m[1][1] = factor.b;
+        s.m[2][2] = factor.c;
+        s
+    }
+
+    // rotations are in degrees, counter-clockwise looking down the axis.
+    pub fn rotate_x(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Matrix4::identity();
+        r.m[1][1] = cos;
+        r.m[1][2] = -sin;
+        r.m[2][1] = sin;
+        r.m[2][2] = cos;
+        r
+    }
+
+    pub fn rotate_y(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Matrix4::identity();
+        r.m[0][0] = cos;
+        r.m[0][2] = sin;
+        r.m[2][0] = -sin;
+        r.m[2][2] = cos;
+        r
+    }
+
+    pub fn rotate_z(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut r = Matrix4::identity();
+        r.m[0][0] = cos;
+        r.m[0][1] = -sin;
+        r.m[1][0] = sin;
+        r.m[1][1] = cos;
+        r
+    }
+
+    // x, then y, then z.
+    pub fn rotate(degrees: Point) -> Self {
+        Matrix4::rotate_z(degrees.c) * Matrix4::rotate_y(degrees.b) * Matrix4::rotate_x(degrees.a)
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Matrix4::identity();
+        for (i, row) in self.m.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                t.m[j][i] = *value;
+            }
+        }
+        t
+    }
+
+    // gauss-jordan elimination with partial pivoting. None if the matrix
+    // squashes space flat (like a scale of 0) and can't be undone.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+
+        for col in 0..4 {
+            // the biggest value in the column keeps rounding errors down.
+            let pivot = (col..4).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+
+                let f = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+
+        Some(Matrix4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        Point::new(
+            m[0][0] * p.a + m[0][1] * p.b + m[0][2] * p.c + m[0][3],
+            m[1][0] * p.a + m[1][1] * p.b + m[1][2] * p.c + m[1][3],
+            m[2][0] * p.a + m[2][1] * p.b + m[2][2] * p.c + m[2][3],
+        )
+    }
+
+    // directions ignore the translation.
+    pub fn transform_vector(&self, v: Point) -> Point {
+        let m = &self.m;
+        Point::new(
+            m[0][0] * v.a + m[0][1] * v.b + m[0][2] * v.c,
+            m[1][0] * v.a + m[1][1] * v.b + m[1][2] * v.c,
+            m[2][0] * v.a + m[2][1] * v.b + m[2][2] * v.c,
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut out = [[0.0; 4]; 4];
+
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        Matrix4 { m: out }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Matrix4, b: Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-5, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn assert_point_near(a: Point, b: Point) {
+        assert!((a + b.scalar_mul(-1.0)).len() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let m = Matrix4::translate(Point::new(1.0, -2.0, 3.0))
+            * Matrix4::rotate(Point::new(30.0, 45.0, -60.0))
+            * Matrix4::scale(Point::new(2.0, 0.5, 3.0));
+        let inv = m.inverse().unwrap();
+
+        assert_near(m * inv, Matrix4::identity());
+        assert_near(inv * m, Matrix4::identity());
+
+        let p = Point::new(0.3, -1.2, 4.0);
+        assert_point_near(inv.transform_point(m.transform_point(p)), p);
+    }
+
+    #[test]
+    fn inverse_needs_pivoting() {
+        // a 0 on the diagonal, fine once the rows are swapped.
+        let m = Matrix4::rotate_z(90.0);
+        assert_near(m * m.inverse().unwrap(), Matrix4::identity());
+    }
+
+    #[test]
+    fn flat_transforms_have_no_inverse() {
+        assert!(Matrix4::scale(Point::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn order_of_application() {
+        // scale first, then move.
+        let m = Matrix4::translate(Point::new(1.0, 0.0, 0.0)) * Matrix4::scale(Point::new(2.0, 2.0, 2.0));
+        assert_point_near(m.transform_point(Point::new(1.0, 1.0, 1.0)), Point::new(3.0, 2.0, 2.0));
+
+        // counter-clockwise, x turns into y.
+        assert_point_near(Matrix4::rotate_z(90.0).transform_vector(Point::new(1.0, 0.0, 0.0)), Point::new(0.0, 1.0, 0.0));
+        // vectors don't move.
+        assert_point_near(m.transform_vector(Point::new(1.0, 0.0, 0.0)), Point::new(2.0, 0.0, 0.0));
+    }
+}
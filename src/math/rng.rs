@@ -0,0 +1,39 @@
+// PCG32 (o'neill), a small fast generator that is the same on every
+// platform. each stream is an independent sequence, so every pixel sample
+// can have its own without them overlapping.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Default for Pcg32 {
+    fn default() -> Self {
+        Pcg32::new(0, 0)
+    }
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 { state: 0, increment: (stream << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Pcg32::MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    // uniform in [0, 1), from the top 24 bits so every value is exact.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
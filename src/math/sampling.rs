@@ -0,0 +1,63 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::math::vector::*;
+
+/*
+ *  Warps from uniform numbers in [0, 1)^2 to the shapes rays need. They
+ *  keep well spread inputs (stratified, low discrepancy) well spread.
+ *  Directions come out in a local frame with z up, see Point::to_frame.
+ */
+
+// concentric mapping (shirley & chiu), squares turn into rings without
+// squashing the strata near the center like r = sqrt(u) does.
+pub fn uniform_disk(u: (f32, f32)) -> (f32, f32) {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+
+    (r * theta.cos(), r * theta.sin())
+}
+
+// pdf cos(theta) / pi. the disk is lifted onto the hemisphere (malley's method).
+pub fn cosine_hemisphere(u: (f32, f32)) -> Point {
+    let (x, y) = uniform_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+    Point::new(x, y, z)
+}
+
+// pdf 1 / (4 pi).
+pub fn uniform_sphere(u: (f32, f32)) -> Point {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Point::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// pdf 1 / (2 pi (1 - cos_max)), inside the cone around z.
+pub fn uniform_cone(u: (f32, f32), cos_max: f32) -> Point {
+    let z = 1.0 + u.0 * (cos_max - 1.0);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Point::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// barycentric weights (b1, b2) of a uniform point in a triangle, by folding
+// the square's far half back over.
+pub fn uniform_triangle(u: (f32, f32)) -> (f32, f32) {
+    if u.0 + u.1 > 1.0 {
+        (1.0 - u.0, 1.0 - u.1)
+    } else {
+        u
+    }
+}
@@ -1,5 +1,5 @@
-use crate::math::random_f32;
 
+use std::fmt;
 use std::ops::Add;
 use std::ops::Mul;
 
@@ -22,39 +22,6 @@ impl<T: Add + Mul> Vector3<T> {
 }
 
 impl Vector3<f32> {
-    pub fn random(min: f32, max: f32) -> Vector3<f32> {
-        Vector3 { a: random_f32(min, max), b: random_f32(min, max), c: random_f32(min, max) }
-    }
-
-    pub fn rand_in_unit_sphere() -> Point {
-        // random length scattering 
-        // for Lambertian Distribution.
-        loop {
-            let s: Point = Vector3::random(-1.0, 1.0);
-            if s.len() * s.len() < 1.0 {
-                return s;
-            }
-        }
-    }
-
-    pub fn rand_unit_vec() -> Point {
-        // unit length scattering 
-        // Used for a more proper Lambertian Distribution of light ray diffusion (Incorrect)
-        Point::rand_in_unit_sphere()
-    }
-
-    pub fn rand_in_hemisphere(normal: Point) -> Point {
-        // hemispherical scattering
-        // diffusion in same hemisphere as normal
-        let in_unit_sphere: Point = Vector3::rand_in_unit_sphere();
-
-        if Vector3::dot(&in_unit_sphere, &normal) > 0.0 {
-            return in_unit_sphere
-        }
-
-        in_unit_sphere.scalar_mul(-1.0)
-    }
-
     pub fn near_zero(self) -> bool {
         // to avoid normal - scatter vector = 0
         let s = 1e-8;
@@ -67,7 +34,7 @@ impl Vector3<f32> {
 
     pub fn reflect(&self, normal: Vector3<f32>) -> Point {
         // simulate metal reflection:
-        // reflection = v + 2*b.
+        // reflection = v - 2*b.
         // v is self.
         // b is the normal vector of length v dot n
         //
@@ -75,20 +42,24 @@ impl Vector3<f32> {
         // n: (0.5, 0.5, 0.5)
 
         let b: Point = normal.scalar_mul(Vector3::dot(self, &normal));
-        let refl: Point = *self + b.scalar_mul(2.0);
+        let refl: Point = *self + b.scalar_mul(-2.0);
         refl
     }
 
-    pub fn origin() -> Self {
-        Vector3::new(0.0, 0.0, 0.0)
-    }
+    pub fn refract(&self, normal: Vector3<f32>, etai_over_etat: f32) -> Point {
+        // snell's law, split into the parts perpendicular and
+        // parallel to the normal. self and normal are unit length,
+        // and normal points against self.
+        let cos_theta: f32 = Vector3::dot(&self.scalar_mul(-1.0), &normal).min(1.0);
+
+        let perpendicular: Point = (*self + normal.scalar_mul(cos_theta)).scalar_mul(etai_over_etat);
+        let parallel: Point = normal.scalar_mul(-(1.0 - Vector3::dot(&perpendicular, &perpendicular)).abs().sqrt());
 
-    pub fn to_string(self) -> String {
-        format!("({}, {}, {})", self.a, self.b, self.c)
+        perpendicular + parallel
     }
 
-    pub fn to_pixel(self) -> String {
-        format!("{} {} {}\n", self.a as i32, self.b as i32, self.c as i32)
+    pub fn origin() -> Self {
+        Vector3::new(0.0, 0.0, 0.0)
     }
 
     pub fn scalar_mul(self, factor: f32) -> Vector3<f32> {
@@ -111,6 +82,32 @@ impl Vector3<f32> {
         )
     }
 
+    // any two unit vectors perpendicular to self (unit length) and each
+    // other. the helper axis is whichever world axis self is furthest from,
+    // so the cross product never vanishes.
+    pub fn tangent_frame(self) -> (Point, Point) {
+        let helper = if self.a.abs() > 0.9 { Point::new(0.0, 1.0, 0.0) } else { Point::new(1.0, 0.0, 0.0) };
+
+        let tangent = helper.cross(self).unit();
+        let bitangent = self.cross(tangent);
+
+        (tangent, bitangent)
+    }
+
+    // turns a direction given with z along self (unit length) into world
+    // space, for the warps in math::sampling.
+    pub fn to_frame(self, local: Point) -> Point {
+        let (tangent, bitangent) = self.tangent_frame();
+        tangent.scalar_mul(local.a) + bitangent.scalar_mul(local.b) + self.scalar_mul(local.c)
+    }
+
+    // the other way round from to_frame: a world direction given with z
+    // along self (unit length).
+    pub fn from_frame(self, world: Point) -> Point {
+        let (tangent, bitangent) = self.tangent_frame();
+        Point::new(Vector3::dot(&world, &tangent), Vector3::dot(&world, &bitangent), Vector3::dot(&world, &self))
+    }
+
     pub fn unit(self) -> Self {
         self.scalar_div(self.len())
     }
@@ -143,3 +140,9 @@ impl<T: Add<Output = T> + Mul<Output = T>> Mul for Vector3<T> {
         )
     }
 }
+
+impl fmt::Display for Vector3<f32> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.a, self.b, self.c)
+    }
+}
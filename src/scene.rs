@@ -0,0 +1,737 @@
+pub mod lexer;
+pub mod parser;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::math::matrix::Matrix4;
+use crate::math::vector::*;
+use crate::scene::lexer::Position;
+use crate::scene::parser::*;
+use crate::shapes::instance::Instance;
+use crate::shapes::motion::Motion;
+use crate::shapes::plane::Plane;
+use crate::shapes::quad::Quad;
+use crate::shapes::shape::Shape;
+use crate::shapes::sphere::Sphere;
+use crate::shapes::triangle::Triangle;
+use crate::util::bvh::SplitMethod;
+use crate::util::camera::{Camera, Focus, Lens};
+use crate::util::environment::Environment;
+use crate::util::hittable::World;
+use crate::util::image::{Image, Progressive};
+use crate::util::integrator::IntegratorKind;
+use crate::util::sampler::SamplerKind;
+use crate::util::material::Material;
+use crate::util::obj::load_obj;
+use crate::util::pbr::Pbr;
+use crate::util::noise::{Noise, Pattern, Perlin};
+use crate::util::texture::{Checker, Filter, ImageOptions, Mapping, Texture, TextureCache, Wrap};
+
+/*
+ *  Scene description files. See scenes/default.scene for an example.
+ */
+
+// Everything needed to render: what the image looks like, where the
+// camera is, and what's in the world.
+pub struct Scene {
+    pub image: Image,
+    pub camera: Camera,
+    pub world: World,
+    look: (Point, Point, Point), // camera up, at, to; for rebuilding the camera.
+    lens: Lens,
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SceneError {
+    pub fn new(pos: Position, message: &str) -> Self {
+        SceneError { line: pos.line, column: pos.column, message: message.to_owned() }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// statements that put a shape in the world.
+const SHAPES: [&str; 6] = ["sphere", "triangle", "plane", "quad", "rect", "mesh"];
+
+// named things that later statements can refer to.
+struct Library {
+    textures: HashMap<String, Texture>,
+    images: TextureCache, // decoded image files, shared by every texture that uses one.
+    materials: HashMap<String, Material>,
+    shapes: HashMap<String, Arc<Shape>>,
+}
+
+impl Library {
+    fn texture(&self, (name, pos): (String, Position)) -> Result<Texture, SceneError> {
+        self.textures.get(&name)
+            .cloned()
+            .ok_or_else(|| SceneError::new(pos, &format!("no texture named '{}'", name)))
+    }
+
+    fn material(&self, (name, pos): (String, Position)) -> Result<Material, SceneError> {
+        self.materials.get(&name)
+            .cloned()
+            .ok_or_else(|| SceneError::new(pos, &format!("no material named '{}'", name)))
+    }
+
+    fn shape(&self, (name, pos): (String, Position)) -> Result<Arc<Shape>, SceneError> {
+        self.shapes.get(&name)
+            .cloned()
+            .ok_or_else(|| SceneError::new(pos, &format!("no shape named '{}'", name)))
+    }
+}
+
+impl Scene {
+    // images are decoded through textures, so its missing_fallback decides
+    // whether a bad image stops the load.
+    pub fn load(file_name: &str, textures: TextureCache) -> io::Result<Scene> {
+        let source = fs::read_to_string(file_name)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", file_name, e)))?;
+
+        let base_dir = Path::new(file_name).parent().unwrap_or(Path::new(""));
+
+        Scene::parse(&source, base_dir, textures)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}:{}", file_name, e)))
+    }
+
+    // file paths in the scene are relative to base_dir.
+    pub fn parse(source: &str, base_dir: &Path, textures: TextureCache) -> Result<Scene, SceneError> {
+        let statements = parse(source)?;
+
+        let mut library = Library { textures: HashMap::new(), images: textures, materials: HashMap::new(), shapes: HashMap::new() };
+        let mut image: Option<Image> = None;
+        let mut camera: Option<(Point, Point, Point)> = None;
+        let mut lens = Lens::pinhole();
+        let mut shutter = (0.0, 1.0);
+        let mut world = World::new();
+
+        for mut statement in statements {
+            let pos = statement.pos;
+            let block = &mut statement.block;
+
+            match (statement.keyword.as_str(), statement.name, statement.variant) {
+                ("image", None, None) => {
+                    if image.is_some() {
+                        return Err(SceneError::new(pos, "only one image block is allowed"));
+                    }
+                    image = Some(Scene::image(block)?);
+                },
+                ("camera", None, None) => {
+                    if camera.is_some() {
+                        return Err(SceneError::new(pos, "only one camera block is allowed"));
+                    }
+
+                    let up = block.vector("up")?.unwrap_or(Point::new(0.0, 1.0, 0.0));
+                    let at = block.vector("at")?;
+                    let to = block.vector("to")?;
+                    camera = Some((up, block.require("at", at)?, block.require("to", to)?));
+                    lens = Scene::lens(block, pos)?;
+
+                    let open = block.number("shutter_open")?.unwrap_or(0.0);
+                    let close = block.number("shutter_close")?.unwrap_or(1.0);
+                    if close < open {
+                        return Err(SceneError::new(pos, "the shutter can't close before it opens"));
+                    }
+                    shutter = (open, close);
+                },
+                ("background", None, None) => {
+                    world.environment = Scene::environment(block, base_dir, &mut library, pos)?;
+                },
+                ("texture", Some(name), Some(variant)) => {
+                    let texture = Scene::texture(&variant, block, base_dir, &mut library, pos)?;
+
+                    if library.textures.insert(name.clone(), texture).is_some() {
+                        return Err(SceneError::new(pos, &format!("texture '{}' is already defined", name)));
+                    }
+                },
+                ("material", Some(name), Some(variant)) => {
+                    let material = Scene::material(&variant, block, &library, pos)?;
+
+                    if library.materials.insert(name.clone(), material).is_some() {
+                        return Err(SceneError::new(pos, &format!("material '{}' is already defined", name)));
+                    }
+                },
+                ("shape", Some(name), Some(kind)) => {
+                    let shape = Scene::shape(&kind, block, &mut library, base_dir, pos)?;
+
+                    if library.shapes.insert(name.clone(), Arc::new(shape)).is_some() {
+                        return Err(SceneError::new(pos, &format!("shape '{}' is already defined", name)));
+                    }
+                },
+                ("instance", None, None) => {
+                    let name = block.ident("shape")?;
+                    let object = library.shape(block.require("shape", name)?)?;
+                    let transform = Scene::transform(block)?.unwrap_or_default();
+
+                    world.insert(Scene::place(object, transform, pos)?);
+                },
+                (kind, None, None) if SHAPES.contains(&kind) => {
+                    let shape = Scene::shape(kind, block, &mut library, base_dir, pos)?;
+
+                    match Scene::transform(block)? {
+                        Some(transform) => world.insert(Scene::place(Arc::new(shape), transform, pos)?),
+                        None => world.insert(shape),
+                    }
+                },
+                (keyword, None, None) if keyword == "texture" || keyword == "material" || keyword == "shape" => {
+                    return Err(SceneError::new(pos, &format!("{} needs a name and a type, like '{} name = type {{ ... }}'", keyword, keyword)));
+                },
+                (keyword, None, None) => {
+                    return Err(SceneError::new(pos, &format!("unknown statement '{}'", keyword)));
+                },
+                (keyword, _, _) => {
+                    return Err(SceneError::new(pos, &format!("'{}' can't be named", keyword)));
+                },
+            }
+
+            block.finish()?;
+        }
+
+        let start = Position { line: 1, column: 1 };
+        let image = image.ok_or_else(|| SceneError::new(start, "the scene needs an image block"))?;
+        let (up, at, to) = camera.ok_or_else(|| SceneError::new(start, "the scene needs a camera block"))?;
+
+        world.build_bvh(SplitMethod::Sah);
+
+        let mut camera = Camera::new_lens(up, at, to, image, lens);
+        camera.shutter = shutter;
+
+        Ok(Scene { image, camera, world, look: (up, at, to), lens })
+    }
+
+    // changing the resolution changes the aspect ratio, so the camera has
+    // to be rebuilt too.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.image.width = width;
+        self.image.height = height;
+
+        let (up, at, to) = self.look;
+        let shutter = self.camera.shutter;
+
+        self.camera = Camera::new_lens(up, at, to, self.image, self.lens);
+        self.camera.shutter = shutter;
+    }
+
+    fn image(block: &mut Block) -> Result<Image, SceneError> {
+        let width = block.positive_count("width")?;
+        let width = block.require("width", width)?;
+
+        // height wins over aspect, 16:10 if neither is given.
+        let aspect = block.positive("aspect")?.unwrap_or(16.0 / 10.0);
+        let height = block.positive_count("height")?.unwrap_or(((width as f32 / aspect) as i32).max(1));
+
+        let fov = block.number_where("fov", |f| f > 0.0 && f < 180.0, "between 0 and 180")?.unwrap_or(80.0);
+        let samples = block.positive_count("samples")?.unwrap_or(10);
+
+        let mut image = Image::new(width, height, fov, samples);
+        if let Some(max_depth) = block.count("max_depth")? {
+            image.max_depth = max_depth;
+        }
+        if let Some(threads) = block.count("threads")? {
+            image.threads = threads as usize;
+        }
+        if let Some(seed) = block.seed("seed")? {
+            image.seed = Some(seed);
+        }
+        if let Some((name, pos)) = block.ident("integrator")? {
+            image.integrator = IntegratorKind::from_name(&name)
+                .ok_or_else(|| SceneError::new(pos, &format!("unknown integrator '{}' (expected one of {})", name, IntegratorKind::NAMES)))?;
+        }
+        if let Some((name, pos)) = block.ident("sampler")? {
+            image.sampler = SamplerKind::from_name(&name)
+                .ok_or_else(|| SceneError::new(pos, &format!("unknown sampler '{}' (expected one of {})", name, SamplerKind::NAMES)))?;
+        }
+        if let Some(distance) = block.number("ao_distance")? {
+            match &mut image.integrator {
+                IntegratorKind::AmbientOcclusion(ao) => ao.distance = distance,
+                _ => return Err(SceneError::new(block.pos, "ao_distance needs 'integrator = ao'")),
+            }
+        }
+        // any of these renders in passes, see Progressive.
+        let pass_samples = block.positive_count("pass_samples")?;
+        let write_every = block.number("write_every")?;
+        let time_limit = block.number("time_limit")?;
+        if pass_samples.is_some() || write_every.is_some() || time_limit.is_some() {
+            let mut progressive = Progressive::default();
+            if let Some(samples) = pass_samples {
+                progressive.pass_samples = samples;
+            }
+            if let Some(seconds) = write_every {
+                progressive.write_interval = Duration::from_secs_f32(seconds.max(0.0));
+            }
+            progressive.time_limit = time_limit.map(|seconds| Duration::from_secs_f32(seconds.max(0.0)));
+            image.progressive = Some(progressive);
+        }
+        if let Some(depth) = block.count("roulette_depth")? {
+            match &mut image.integrator {
+                IntegratorKind::Path(path) => path.roulette_depth = depth,
+                _ => return Err(SceneError::new(block.pos, "roulette_depth needs 'integrator = path'")),
+            }
+        }
+
+        Ok(image)
+    }
+
+    // the geometry of a shape statement, without any transform.
+    fn shape(kind: &str, block: &mut Block, library: &mut Library, base_dir: &Path, pos: Position) -> Result<Shape, SceneError> {
+        match kind {
+            "sphere" => {
+                let center = block.vector("center")?;
+                let radius = block.number("radius")?;
+                let material = block.ident("material")?;
+
+                let center = block.require("center", center)?;
+                let material = library.material(block.require("material", material)?)?;
+                let radius = block.require("radius", radius)?;
+
+                // each path point is somewhere the sphere passes through,
+                // evenly spaced in time after center.
+                let path = block.vectors("path")?;
+                let sphere = if path.is_empty() {
+                    Sphere::new_pos_t(center, material, radius)
+                } else {
+                    let points: Vec<Point> = std::iter::once(center).chain(path).collect();
+                    Sphere::moving(Motion::path(&points).unwrap(), material, radius)
+                };
+
+                Ok(Shape::sphere(sphere))
+            },
+            "triangle" => {
+                let mut corner = |key: &str| -> Result<Point, SceneError> {
+                    let v = block.vector(key)?;
+                    block.require(key, v)
+                };
+                let vertices = [corner("a")?, corner("b")?, corner("c")?];
+
+                let material = block.ident("material")?;
+                let material = library.material(block.require("material", material)?)?;
+
+                Ok(Shape::triangle(Triangle::new(vertices, material)))
+            },
+            "plane" => {
+                let point = block.vector("point")?;
+                let normal = block.vector("normal")?;
+                let material = block.ident("material")?;
+
+                Ok(Shape::plane(Plane::new(
+                    block.require("point", point)?,
+                    block.require("normal", normal)?,
+                    library.material(block.require("material", material)?)?
+                )))
+            },
+            "quad" => {
+                let origin = block.vector("origin")?;
+                let u = block.vector("u")?;
+                let v = block.vector("v")?;
+                let material = block.ident("material")?;
+
+                let quad = Quad::new(
+                    block.require("origin", origin)?,
+                    block.require("u", u)?,
+                    block.require("v", v)?,
+                    library.material(block.require("material", material)?)?
+                );
+
+                if !quad.has_area() {
+                    return Err(SceneError::new(pos, "a quad's u and v can't be 0 or parallel"));
+                }
+
+                Ok(Shape::quad(quad))
+            },
+            "rect" => {
+                let min = block.vector("min")?;
+                let max = block.vector("max")?;
+                let material = block.ident("material")?;
+
+                let rect = Quad::rect(
+                    block.require("min", min)?,
+                    block.require("max", max)?,
+                    library.material(block.require("material", material)?)?
+                ).ok_or_else(|| SceneError::new(pos, "a rect has to be flat along one axis"))?;
+
+                if !rect.has_area() {
+                    return Err(SceneError::new(pos, "a rect can only be flat along one axis"));
+                }
+
+                Ok(Shape::quad(rect))
+            },
+            "mesh" => {
+                let file = block.string("file")?;
+                let path = base_dir.join(block.require("file", file)?);
+
+                // used for faces that don't pick a material in the .mtl
+                let material = match block.ident("material")? {
+                    Some(m) => library.material(m)?,
+                    None => Material::Lambertian(Texture::Solid(Color::new(0.8, 0.8, 0.8))),
+                };
+
+                load_obj(&path.to_string_lossy(), material, &mut library.images)
+                    .map_err(|e| SceneError::new(pos, &format!("could not load mesh: {}", e)))
+            },
+            _ => Err(SceneError::new(pos, &format!("unknown shape type '{}' (expected one of {})", kind, SHAPES.join(", ")))),
+        }
+    }
+
+    // scale, then rotate (degrees around x, y, z), then translate. None if
+    // the block has none of them.
+    fn transform(block: &mut Block) -> Result<Option<Matrix4>, SceneError> {
+        let translate = block.vector("translate")?;
+        let rotate = block.vector("rotate")?;
+        let scale = block.vector("scale")?;
+
+        if translate.is_none() && rotate.is_none() && scale.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            Matrix4::translate(translate.unwrap_or(Point::origin()))
+                * Matrix4::rotate(rotate.unwrap_or(Point::origin()))
+                * Matrix4::scale(scale.unwrap_or(Point::new(1.0, 1.0, 1.0)))
+        ))
+    }
+
+    fn place(object: Arc<Shape>, transform: Matrix4, pos: Position) -> Result<Shape, SceneError> {
+        Instance::new(object, transform)
+            .map(Shape::instance)
+            .ok_or_else(|| SceneError::new(pos, "the transform flattens the shape, is a scale 0?"))
+    }
+
+    // depth of field. without a focus_distance the lens focuses on 'to'.
+    fn lens(block: &mut Block, pos: Position) -> Result<Lens, SceneError> {
+        let mut lens = Lens::pinhole();
+
+        if let Some(aperture) = block.number("aperture")? {
+            if aperture < 0.0 {
+                return Err(SceneError::new(pos, "aperture can't be negative"));
+            }
+            lens.aperture = aperture;
+        }
+        if let Some(distance) = block.number("focus_distance")? {
+            if distance <= 0.0 {
+                return Err(SceneError::new(pos, "focus_distance has to be above 0"));
+            }
+            lens.focus = Focus::Distance(distance);
+        }
+        if let Some(blades) = block.count("blades")? {
+            lens.blades = blades as u32;
+        }
+        if let Some(rotation) = block.number("blade_rotation")? {
+            lens.blade_rotation = rotation;
+        }
+
+        Ok(lens)
+    }
+
+    // a color as key = (r, g, b), or another texture as key_texture = name.
+    fn color_or_texture(key: &str, block: &mut Block, library: &Library) -> Result<Option<Texture>, SceneError> {
+        let texture_key = format!("{}_texture", key);
+
+        match (block.vector(key)?, block.ident(&texture_key)?) {
+            (Some(color), None) => Ok(Some(Texture::Solid(color))),
+            (None, Some(name)) => Ok(Some(library.texture(name)?)),
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(SceneError::new(block.pos, &format!("give one of '{}' or '{}'", key, texture_key))),
+        }
+    }
+
+    // filtering, wrapping and uv placement of an image texture.
+    fn image_options(block: &mut Block) -> Result<ImageOptions, SceneError> {
+        let mut options = ImageOptions::default();
+
+        if let Some((name, pos)) = block.ident("filter")? {
+            options.filter = Filter::from_name(&name)
+                .ok_or_else(|| SceneError::new(pos, &format!("unknown filter '{}' (expected one of {})", name, Filter::NAMES)))?;
+        }
+        if let Some((name, pos)) = block.ident("wrap")? {
+            options.wrap = Wrap::from_name(&name)
+                .ok_or_else(|| SceneError::new(pos, &format!("unknown wrap '{}' (expected one of {})", name, Wrap::NAMES)))?;
+        }
+
+        // scale sets both directions, scale_u and scale_v one each.
+        let scale = block.number("scale")?.unwrap_or(1.0);
+        options.scale = (block.number("scale_u")?.unwrap_or(scale), block.number("scale_v")?.unwrap_or(scale));
+        options.offset = (block.number("offset_u")?.unwrap_or(0.0), block.number("offset_v")?.unwrap_or(0.0));
+
+        if let Some(srgb) = block.flag("srgb")? {
+            options.srgb = srgb;
+        }
+
+        Ok(options)
+    }
+
+    fn texture(variant: &str, block: &mut Block, base_dir: &Path, library: &mut Library, pos: Position) -> Result<Texture, SceneError> {
+        match variant {
+            "solid" => {
+                let color = block.vector("color")?;
+                Ok(Texture::Solid(block.require("color", color)?))
+            },
+            "image" => {
+                let file = block.string("file")?;
+                let path: PathBuf = base_dir.join(block.require("file", file)?);
+                let options = Scene::image_options(block)?;
+
+                library.images.load(&path, options)
+                    .map_err(|e| SceneError::new(pos, &e.to_string()))
+            },
+            "checker" => {
+                let even = Scene::color_or_texture("even", block, library)?.unwrap_or(Texture::Solid(Color::new(0.2, 0.2, 0.2)));
+                let odd = Scene::color_or_texture("odd", block, library)?.unwrap_or(Texture::Solid(Color::new(0.9, 0.9, 0.9)));
+                let mapping = match block.ident("mapping")? {
+                    Some((name, pos)) => Mapping::from_name(&name)
+                        .ok_or_else(|| SceneError::new(pos, &format!("unknown mapping '{}' (expected one of {})", name, Mapping::NAMES)))?,
+                    None => Mapping::Object,
+                };
+                // uvs run 0 to 1, so 8 squares across by default.
+                let size = block.positive("size")?.unwrap_or(if mapping == Mapping::Uv { 0.125 } else { 1.0 });
+
+                Ok(Texture::Checker(Arc::new(Checker { even, odd, size, mapping })))
+            },
+            "noise" | "turbulence" | "marble" | "wood" => {
+                let (pattern, low, high) = match variant {
+                    "noise" => (Pattern::Fbm, Color::origin(), Color::new(1.0, 1.0, 1.0)),
+                    "turbulence" => (Pattern::Turbulence, Color::origin(), Color::new(1.0, 1.0, 1.0)),
+                    "marble" => (Pattern::Marble, Color::new(0.1, 0.1, 0.12), Color::new(0.95, 0.95, 0.92)),
+                    _ => (Pattern::Wood, Color::new(0.76, 0.55, 0.33), Color::new(0.36, 0.2, 0.09)),
+                };
+
+                // the same seed gives the same pattern.
+                let seed = block.count("seed")?.unwrap_or(0);
+                let octaves = block.count("octaves")?.unwrap_or(if pattern == Pattern::Fbm { 1 } else { 7 });
+
+                Ok(Texture::Noise(Arc::new(Noise {
+                    perlin: Perlin::new(seed as u64),
+                    pattern,
+                    scale: block.number("scale")?.unwrap_or(1.0),
+                    octaves: octaves.max(1) as u32,
+                    low: block.vector("low")?.unwrap_or(low),
+                    high: block.vector("high")?.unwrap_or(high),
+                })))
+            },
+            _ => Err(SceneError::new(pos, &format!("unknown texture type '{}' (expected solid, image, checker, noise, turbulence, marble or wood)", variant))),
+        }
+    }
+
+    // a plain color, a gradient from bottom to top, or an equirectangular
+    // image given by file or texture name.
+    fn environment(block: &mut Block, base_dir: &Path, library: &mut Library, pos: Position) -> Result<Environment, SceneError> {
+        let color = block.vector("color")?;
+        let (bottom, top) = (block.vector("bottom")?, block.vector("top")?);
+        let (file, texture) = (block.string("file")?, block.ident("texture")?);
+        let rotation = block.number("rotation")?;
+        let intensity = block.number("intensity")?;
+
+        let map = match (file, texture) {
+            (Some(file), None) => {
+                let path: PathBuf = base_dir.join(file);
+
+                Some(library.images.load(&path, ImageOptions::default())
+                    .map_err(|e| SceneError::new(pos, &format!("environment map: {}", e)))?)
+            },
+            (None, Some(name)) => Some(library.texture(name)?),
+            (None, None) => None,
+            (Some(_), Some(_)) => return Err(SceneError::new(pos, "background takes 'file' or 'texture', not both")),
+        };
+
+        if map.is_none() && (rotation.is_some() || intensity.is_some()) {
+            return Err(SceneError::new(pos, "'rotation' and 'intensity' only apply to an environment map"));
+        }
+
+        match (color, bottom.is_some() || top.is_some(), map) {
+            (Some(color), false, None) => Ok(Environment::Constant(color)),
+            // a missing end keeps the default sky's color.
+            (None, true, None) => Ok(Environment::Gradient {
+                bottom: bottom.unwrap_or(Color::new(1.0, 1.0, 1.0)),
+                top: top.unwrap_or(Color::new(0.5, 0.7, 1.0)),
+            }),
+            (None, false, Some(texture)) => Ok(Environment::Map {
+                texture,
+                rotation: rotation.unwrap_or(0.0),
+                intensity: intensity.unwrap_or(1.0),
+            }),
+            _ => Err(SceneError::new(pos, "background needs exactly one of 'color', 'top'/'bottom', or 'file'/'texture'")),
+        }
+    }
+
+    // either a named texture or a plain color.
+    fn texture_or_color(variant: &str, block: &mut Block, library: &Library) -> Result<Texture, SceneError> {
+        match (block.ident("texture")?, block.vector("color")?) {
+            (Some(name), None) => library.texture(name),
+            (None, Some(color)) => Ok(Texture::Solid(color)),
+            _ => Err(SceneError::new(block.pos, &format!("{} needs one of 'texture' or 'color'", variant))),
+        }
+    }
+
+    // a 0 to 1 amount, as key = number or key_texture = name.
+    fn amount_or_texture(key: &str, block: &mut Block, library: &Library) -> Result<Option<Texture>, SceneError> {
+        let texture_key = format!("{}_texture", key);
+
+        match (block.number(key)?, block.ident(&texture_key)?) {
+            (Some(amount), None) => Ok(Some(Texture::Solid(Color::new(amount, amount, amount)))),
+            (None, Some(name)) => Ok(Some(library.texture(name)?)),
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(SceneError::new(block.pos, &format!("give one of '{}' or '{}'", key, texture_key))),
+        }
+    }
+
+    fn material(variant: &str, block: &mut Block, library: &Library, pos: Position) -> Result<Material, SceneError> {
+        match variant {
+            "lambertian" => Ok(Material::Lambertian(Scene::texture_or_color(variant, block, library)?)),
+            "metal" => {
+                // how blurry reflections are, 0 (a mirror) to 1.
+                let albedo = Scene::texture_or_color(variant, block, library)?;
+                let fuzz = block.number_where("fuzz", |f| f >= 0.0, "0 or more")?.unwrap_or(0.0);
+
+                Ok(Material::Metal { albedo, fuzz })
+            },
+            "dielectric" => {
+                let ior = block.positive("ior")?;
+                Ok(Material::Dielectric(block.require("ior", ior)?))
+            },
+            "light" => {
+                // emitted color, scaled by intensity.
+                let texture = Scene::texture_or_color(variant, block, library)?;
+                let intensity = block.number("intensity")?.unwrap_or(1.0);
+
+                Ok(Material::DiffuseLight { texture, intensity })
+            },
+            "pbr" => {
+                // a conductor's eta and k stand in for its base colour, so
+                // that one is optional.
+                let base_color = match (block.ident("texture")?, block.vector("color")?) {
+                    (Some(name), None) => library.texture(name)?,
+                    (None, Some(color)) => Texture::Solid(color),
+                    (None, None) => Texture::Solid(Color::new(0.8, 0.8, 0.8)),
+                    _ => return Err(SceneError::new(block.pos, "pbr takes one of 'texture' or 'color'")),
+                };
+
+                let roughness = Scene::amount_or_texture("roughness", block, library)?
+                    .unwrap_or(Texture::Solid(Color::new(0.5, 0.5, 0.5)));
+                let metallic = Scene::amount_or_texture("metallic", block, library)?
+                    .unwrap_or(Texture::Solid(Color::origin()));
+                let ior = block.positive("ior")?.unwrap_or(1.5);
+
+                let conductor = match (block.vector("eta")?, block.vector("k")?) {
+                    (Some(eta), Some(k)) => Some((eta, k)),
+                    (None, None) => None,
+                    _ => return Err(SceneError::new(block.pos, "a conductor needs both 'eta' and 'k'")),
+                };
+
+                Ok(Material::Pbr(Pbr { base_color, roughness, metallic, ior, conductor }))
+            },
+            _ => Err(SceneError::new(pos, &format!("unknown material type '{}' (expected lambertian, metal, dielectric, light or pbr)", variant))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "camera { up = (0, 1, 0) at = (0, 0, 1) to = (0, 0, 0) }\n";
+
+    fn load(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse(&format!("{}{}", CAMERA, source), Path::new(""), TextureCache::new(false))
+    }
+
+    fn error(source: &str) -> String {
+        match load(source) {
+            Ok(_) => panic!("'{}' loaded", source),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn image_settings() {
+        let scene = load("image { width = 160 aspect = 2 samples = 3 }").unwrap();
+
+        assert_eq!((scene.image.width, scene.image.height), (160, 80));
+        assert_eq!(scene.image.samples_per_pixel, 3);
+    }
+
+    #[test]
+    fn image_sizes_have_to_be_positive() {
+        assert_eq!(error("image { width = 8 aspect = -2 }"), "2:19: 'aspect' has to be above 0");
+        assert_eq!(error("image { width = 8 aspect = 0 }"), "2:19: 'aspect' has to be above 0");
+        assert_eq!(error("image { width = 0 }"), "2:9: 'width' has to be at least 1");
+        assert_eq!(error("image { width = 8 height = 0 }"), "2:19: 'height' has to be at least 1");
+        assert_eq!(error("image { width = 8 samples = 0 }"), "2:19: 'samples' has to be at least 1");
+        assert_eq!(error("image { width = 8 fov = 0 }"), "2:19: 'fov' has to be between 0 and 180");
+        assert_eq!(error("image { width = 8 fov = 180 }"), "2:19: 'fov' has to be between 0 and 180");
+    }
+
+    #[test]
+    fn seeds_are_kept_exactly() {
+        let seed = |s: &str| load(&format!("image {{ width = 8 seed = {} }}", s)).unwrap().image.seed;
+
+        assert_eq!(seed("18446744073709551615"), Some(u64::MAX));
+        assert_eq!(seed("16777217"), Some(16777217));
+        assert_eq!(error("image { width = 8 seed = 18446744073709551616 }"), "2:19: 'seed' has to be a whole number from 0 to 18446744073709551615");
+        assert_eq!(error("image { width = 8 seed = -1 }"), "2:19: 'seed' has to be a whole number from 0 to 18446744073709551615");
+        assert_eq!(error("image { width = 8 seed = 1.5 }"), "2:19: 'seed' has to be a whole number from 0 to 18446744073709551615");
+    }
+
+    #[test]
+    fn a_wide_aspect_keeps_a_row() {
+        let scene = load("image { width = 4 aspect = 10 }").unwrap();
+        assert_eq!(scene.image.height, 1);
+    }
+
+    #[test]
+    fn materials_are_checked() {
+        let image = "image { width = 8 }\n";
+
+        assert_eq!(error(&format!("{}material m = metal {{ color = (1, 1, 1) fuzz = -1 }}", image)), "3:40: 'fuzz' has to be 0 or more");
+        assert_eq!(error(&format!("{}material m = dielectric {{ ior = 0 }}", image)), "3:27: 'ior' has to be above 0");
+        assert_eq!(error(&format!("{}material m = pbr {{ ior = -1.5 }}", image)), "3:20: 'ior' has to be above 0");
+    }
+
+    #[test]
+    fn quads_need_an_area() {
+        let setup = "image { width = 8 }\nmaterial m = lambertian { color = (1, 1, 1) }\n";
+
+        assert_eq!(
+            error(&format!("{}quad {{ origin = (0, 0, 0) u = (1, 0, 0) v = (2, 0, 0) material = m }}", setup)),
+            "4:1: a quad's u and v can't be 0 or parallel"
+        );
+        assert_eq!(
+            error(&format!("{}rect {{ min = (0, 0, 0) max = (1, 0, 0) material = m }}", setup)),
+            "4:1: a rect can only be flat along one axis"
+        );
+        assert!(load(&format!("{}rect {{ min = (0, 0, 0) max = (1, 1, 0) material = m }}", setup)).is_ok());
+    }
+
+    #[test]
+    fn checker_mappings() {
+        let image = "image { width = 8 }\n";
+
+        assert!(load(&format!("{}texture t = checker {{ mapping = uv }}", image)).is_ok());
+        assert_eq!(
+            error(&format!("{}texture t = checker {{ mapping = world }}", image)),
+            "3:23: unknown mapping 'world' (expected one of object, uv)"
+        );
+        assert_eq!(error(&format!("{}texture t = checker {{ size = 0 }}", image)), "3:23: 'size' has to be above 0");
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert_eq!(error("image { width = 8 widht = 9 }"), "2:19: unknown or repeated setting 'widht'");
+    }
+}
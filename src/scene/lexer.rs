@@ -0,0 +1,199 @@
+use crate::scene::SceneError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Number(f32, String), // and the text it was written as, for whole numbers too big for an f32.
+    Str(String),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Comma,
+    Equals,
+}
+
+// where a token starts in the source, both 1 based.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: Position,
+}
+
+impl TokenKind {
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Ident(s) => format!("'{}'", s),
+            TokenKind::Number(_, text) => format!("number {}", text),
+            TokenKind::Str(s) => format!("string \"{}\"", s),
+            TokenKind::LBrace => "'{'".to_owned(),
+            TokenKind::RBrace => "'}'".to_owned(),
+            TokenKind::LParen => "'('".to_owned(),
+            TokenKind::RParen => "')'".to_owned(),
+            TokenKind::Comma => "','".to_owned(),
+            TokenKind::Equals => "'='".to_owned(),
+        }
+    }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, SceneError> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = source.chars().collect();
+
+    let mut i = 0;
+    let mut pos = Position { line: 1, column: 1 };
+
+    // moves past n chars, keeping line/column up to date.
+    let advance = |i: &mut usize, pos: &mut Position, n: usize| {
+        for _ in 0..n {
+            if chars[*i] == '\n' {
+                pos.line += 1;
+                pos.column = 1;
+            } else {
+                pos.column += 1;
+            }
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = pos;
+
+        if c.is_whitespace() {
+            advance(&mut i, &mut pos, 1);
+            continue;
+        }
+
+        // comments run to the end of the line.
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut pos, 1);
+            }
+            continue;
+        }
+
+        let single = match c {
+            '{' => Some(TokenKind::LBrace),
+            '}' => Some(TokenKind::RBrace),
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            ',' => Some(TokenKind::Comma),
+            '=' => Some(TokenKind::Equals),
+            _ => None,
+        };
+
+        if let Some(kind) = single {
+            tokens.push(Token { kind, pos: start });
+            advance(&mut i, &mut pos, 1);
+            continue;
+        }
+
+        if c == '"' {
+            advance(&mut i, &mut pos, 1);
+
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return Err(SceneError::new(start, "unterminated string")),
+                    Some('"') => break,
+                    Some(&ch) => {
+                        text.push(ch);
+                        advance(&mut i, &mut pos, 1);
+                    }
+                }
+            }
+
+            advance(&mut i, &mut pos, 1); // closing quote
+            tokens.push(Token { kind: TokenKind::Str(text), pos: start });
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            let mut len = 0;
+            while let Some(&ch) = chars.get(i + len) {
+                let exponent_sign = (ch == '-' || ch == '+') && len > 0 && matches!(chars[i + len - 1], 'e' | 'E');
+                let sign = (ch == '-' || ch == '+') && len == 0;
+
+                if ch.is_ascii_digit() || ch == '.' || ch == 'e' || ch == 'E' || sign || exponent_sign {
+                    len += 1;
+                } else {
+                    break;
+                }
+            }
+
+            let text: String = chars[i..i + len].iter().collect();
+            let value: f32 = text.parse()
+                .map_err(|_| SceneError::new(start, &format!("'{}' is not a number", text)))?;
+
+            tokens.push(Token { kind: TokenKind::Number(value, text), pos: start });
+            advance(&mut i, &mut pos, len);
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let mut len = 0;
+            while let Some(&ch) = chars.get(i + len) {
+                if ch.is_alphanumeric() || ch == '_' { len += 1 } else { break }
+            }
+
+            let text: String = chars[i..i + len].iter().collect();
+            tokens.push(Token { kind: TokenKind::Ident(text), pos: start });
+            advance(&mut i, &mut pos, len);
+            continue;
+        }
+
+        return Err(SceneError::new(start, &format!("unexpected character '{}'", c)));
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source).unwrap().into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(kinds("a = (1, -2.5, 3e2) # comment\n\"f.png\" { }"), vec![
+            TokenKind::Ident("a".to_owned()),
+            TokenKind::Equals,
+            TokenKind::LParen,
+            TokenKind::Number(1.0, "1".to_owned()),
+            TokenKind::Comma,
+            TokenKind::Number(-2.5, "-2.5".to_owned()),
+            TokenKind::Comma,
+            TokenKind::Number(300.0, "3e2".to_owned()),
+            TokenKind::RParen,
+            TokenKind::Str("f.png".to_owned()),
+            TokenKind::LBrace,
+            TokenKind::RBrace,
+        ]);
+    }
+
+    #[test]
+    fn positions_count_lines_and_columns() {
+        let tokens = tokenize("a\n  bb = 1").unwrap();
+
+        assert_eq!(tokens[0].pos, Position { line: 1, column: 1 });
+        assert_eq!(tokens[1].pos, Position { line: 2, column: 3 });
+        assert_eq!(tokens[2].pos, Position { line: 2, column: 6 });
+        assert_eq!(tokens[3].pos, Position { line: 2, column: 8 });
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        assert_eq!(tokenize("a = 1\nb = [").unwrap_err().to_string(), "2:5: unexpected character '['");
+        assert_eq!(tokenize("file = \"open\nx").unwrap_err().to_string(), "1:8: unterminated string");
+    }
+}
@@ -0,0 +1,375 @@
+use crate::math::vector::*;
+use crate::scene::SceneError;
+use crate::scene::lexer::*;
+
+/*
+ *  Grammar:
+ *
+ *  scene     := statement*
+ *  statement := IDENT block                      e.g. sphere { ... }
+ *             | IDENT IDENT "=" IDENT block      e.g. material red = lambertian { ... }
+ *  block     := "{" (IDENT "=" value)* "}"
+ *  value     := NUMBER | STRING | IDENT | "(" NUMBER "," NUMBER "," NUMBER ")"
+ */
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f32, String), // the value, and how it was written.
+    Str(String),
+    Ident(String),
+    Vector(Point),
+}
+
+impl Value {
+    fn describe(&self) -> &'static str {
+        match self {
+            Value::Number(..) => "a number",
+            Value::Str(_) => "a string",
+            Value::Ident(_) => "a name",
+            Value::Vector(_) => "a vector",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub pos: Position,
+    used: bool,
+}
+
+// The key = value pairs between braces. Values are taken out by key, and
+// finish() complains about anything left over, so typos don't go unnoticed.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub entries: Vec<Entry>,
+    pub pos: Position, // the opening brace
+}
+
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub keyword: String,
+    pub name: Option<String>, // for "material red = lambertian", "red"
+    pub variant: Option<String>, // and "lambertian"
+    pub block: Block,
+    pub pos: Position,
+}
+
+impl Block {
+    fn take(&mut self, key: &str) -> Option<&Entry> {
+        let entry = self.entries.iter_mut().find(|e| e.key == key && !e.used)?;
+        entry.used = true;
+        Some(entry)
+    }
+
+    fn mismatch(entry: &Entry, expected: &str) -> SceneError {
+        SceneError::new(entry.pos, &format!("'{}' should be {}, found {}", entry.key, expected, entry.value.describe()))
+    }
+
+    pub fn number(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Number(n, _), .. }) => Ok(Some(*n)),
+            Some(e) => Err(Block::mismatch(e, "a number")),
+        }
+    }
+
+    // whole, non-negative numbers like widths and sample counts.
+    pub fn count(&mut self, key: &str) -> Result<Option<i32>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Number(n, _), .. }) if *n >= 0.0 && *n <= i32::MAX as f32 && n.fract() == 0.0 => Ok(Some(*n as i32)),
+            Some(e) => Err(Block::mismatch(e, "a whole number")),
+        }
+    }
+
+    // a random seed, any u64. read from the text as written, since an f32
+    // would quietly round most of them to another seed.
+    pub fn seed(&mut self, key: &str) -> Result<Option<u64>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Number(_, text), pos, .. }) => text.parse().map(Some)
+                .map_err(|_| SceneError::new(*pos, &format!("'{}' has to be a whole number from 0 to {}", key, u64::MAX))),
+            Some(e) => Err(Block::mismatch(e, "a whole number")),
+        }
+    }
+
+    // a number above 0, like an aspect ratio or an ior.
+    pub fn positive(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        self.number_where(key, |n| n > 0.0 && n.is_finite(), "above 0")
+    }
+
+    // a number that passes check, requirement says what check wants.
+    pub fn number_where<F: Fn(f32) -> bool>(&mut self, key: &str, check: F, requirement: &str) -> Result<Option<f32>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Number(n, _), .. }) if check(*n) => Ok(Some(*n)),
+            Some(e @ Entry { value: Value::Number(..), .. }) => Err(SceneError::new(e.pos, &format!("'{}' has to be {}", key, requirement))),
+            Some(e) => Err(Block::mismatch(e, "a number")),
+        }
+    }
+
+    // a whole number of 1 or more, like a width or a sample count.
+    pub fn positive_count(&mut self, key: &str) -> Result<Option<i32>, SceneError> {
+        let pos = self.entries.iter().find(|e| e.key == key && !e.used).map(|e| e.pos);
+
+        match self.count(key)? {
+            Some(0) => Err(SceneError::new(pos.unwrap_or(self.pos), &format!("'{}' has to be at least 1", key))),
+            n => Ok(n),
+        }
+    }
+
+    pub fn string(&mut self, key: &str) -> Result<Option<String>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Str(s), .. }) => Ok(Some(s.clone())),
+            Some(e) => Err(Block::mismatch(e, "a string")),
+        }
+    }
+
+    // names are returned with their position, for "unknown material" errors.
+    pub fn ident(&mut self, key: &str) -> Result<Option<(String, Position)>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Ident(s), pos, .. }) => Ok(Some((s.clone(), *pos))),
+            Some(e) => Err(Block::mismatch(e, "a name")),
+        }
+    }
+
+    // true or false.
+    pub fn flag(&mut self, key: &str) -> Result<Option<bool>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Ident(s), .. }) if s == "true" => Ok(Some(true)),
+            Some(Entry { value: Value::Ident(s), .. }) if s == "false" => Ok(Some(false)),
+            Some(e) => Err(Block::mismatch(e, "true or false")),
+        }
+    }
+
+    pub fn vector(&mut self, key: &str) -> Result<Option<Point>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry { value: Value::Vector(v), .. }) => Ok(Some(*v)),
+            Some(e) => Err(Block::mismatch(e, "a vector like (1, 2, 3)")),
+        }
+    }
+
+    // every value given for a key that may be repeated, in order.
+    pub fn vectors(&mut self, key: &str) -> Result<Vec<Point>, SceneError> {
+        let mut values = vec![];
+        while let Some(v) = self.vector(key)? {
+            values.push(v);
+        }
+        Ok(values)
+    }
+
+    pub fn require<T>(&self, key: &str, value: Option<T>) -> Result<T, SceneError> {
+        value.ok_or_else(|| SceneError::new(self.pos, &format!("missing '{}'", key)))
+    }
+
+    pub fn finish(&self) -> Result<(), SceneError> {
+        match self.entries.iter().find(|e| !e.used) {
+            Some(e) => Err(SceneError::new(e.pos, &format!("unknown or repeated setting '{}'", e.key))),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    end: Position, // for errors at the end of the file
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, SceneError> {
+        match self.tokens.get(self.index) {
+            Some(t) => {
+                self.index += 1;
+                Ok(t.clone())
+            },
+            None => Err(SceneError::new(self.end, &format!("expected {}, found end of file", expected))),
+        }
+    }
+
+    fn unexpected(token: &Token, expected: &str) -> SceneError {
+        SceneError::new(token.pos, &format!("expected {}, found {}", expected, token.kind.describe()))
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token, SceneError> {
+        let token = self.next(expected)?;
+        if token.kind != kind {
+            return Err(Parser::unexpected(&token, expected));
+        }
+        Ok(token)
+    }
+
+    fn ident(&mut self, expected: &str) -> Result<(String, Position), SceneError> {
+        let token = self.next(expected)?;
+        match token.kind {
+            TokenKind::Ident(s) => Ok((s, token.pos)),
+            _ => Err(Parser::unexpected(&token, expected)),
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, SceneError> {
+        let token = self.next("a number")?;
+        match token.kind {
+            TokenKind::Number(n, _) => Ok(n),
+            _ => Err(Parser::unexpected(&token, "a number")),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, SceneError> {
+        let (keyword, pos) = self.ident("a statement like 'sphere' or 'material'")?;
+
+        // named form: keyword name = variant { ... }
+        let (name, variant) = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Ident(_)) => {
+                let (name, _) = self.ident("a name")?;
+                self.expect(TokenKind::Equals, "'='")?;
+                let (variant, _) = self.ident("a type")?;
+                (Some(name), Some(variant))
+            },
+            _ => (None, None),
+        };
+
+        let block = self.block()?;
+        Ok(Statement { keyword, name, variant, block, pos })
+    }
+
+    fn block(&mut self) -> Result<Block, SceneError> {
+        let open = self.expect(TokenKind::LBrace, "'{'")?;
+        let mut entries = vec![];
+
+        loop {
+            if let Some(Token { kind: TokenKind::RBrace, .. }) = self.peek() {
+                self.index += 1;
+                break;
+            }
+
+            let (key, pos) = self.ident("a setting name or '}'")?;
+            self.expect(TokenKind::Equals, "'='")?;
+            let value = self.value()?;
+
+            entries.push(Entry { key, value, pos, used: false });
+        }
+
+        Ok(Block { entries, pos: open.pos })
+    }
+
+    fn value(&mut self) -> Result<Value, SceneError> {
+        let token = self.next("a value")?;
+
+        match token.kind {
+            TokenKind::Number(n, text) => Ok(Value::Number(n, text)),
+            TokenKind::Str(s) => Ok(Value::Str(s)),
+            TokenKind::Ident(s) => Ok(Value::Ident(s)),
+            TokenKind::LParen => {
+                let a = self.number()?;
+                self.expect(TokenKind::Comma, "','")?;
+                let b = self.number()?;
+                self.expect(TokenKind::Comma, "','")?;
+                let c = self.number()?;
+                self.expect(TokenKind::RParen, "')'")?;
+
+                Ok(Value::Vector(Vector3::new(a, b, c)))
+            },
+            _ => Err(Parser::unexpected(&token, "a value")),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Vec<Statement>, SceneError> {
+    let tokens = tokenize(source)?;
+
+    let last_line = source.rsplit('\n').next().unwrap_or("");
+    let end = Position {
+        line: source.matches('\n').count() + 1,
+        column: last_line.chars().count() + 1,
+    };
+
+    let mut parser = Parser { tokens, index: 0, end };
+    let mut statements = vec![];
+
+    while parser.peek().is_some() {
+        statements.push(parser.statement()?);
+    }
+
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    fn block(source: &str) -> Block {
+        parse(source).unwrap().remove(0).block
+    }
+
+    #[test]
+    fn parses_both_statement_forms() {
+        let statements = parse("image { width = 10 }\nmaterial red = lambertian { color = (1, 0, 0) }").unwrap();
+
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].keyword, "image");
+        assert_eq!(statements[0].name, None);
+        assert_eq!(statements[1].name.as_deref(), Some("red"));
+        assert_eq!(statements[1].variant.as_deref(), Some("lambertian"));
+        assert_eq!(statements[1].pos, Position { line: 2, column: 1 });
+    }
+
+    #[test]
+    fn errors_point_at_the_token() {
+        assert_eq!(error("sphere {\n  radius 2\n}"), "2:10: expected '=', found number 2");
+        assert_eq!(error("sphere { center = (1, 2) }"), "1:24: expected ',', found ')'");
+        assert_eq!(error("sphere { radius = }"), "1:19: expected a value, found '}'");
+    }
+
+    #[test]
+    fn errors_at_the_end_of_the_file() {
+        assert_eq!(error("sphere {\n  radius = 1\n"), "3:1: expected a setting name or '}', found end of file");
+    }
+
+    #[test]
+    fn values_have_to_be_the_right_type() {
+        let mut b = block("s { radius = \"big\" count = 1.5 }");
+
+        assert_eq!(b.number("radius").unwrap_err().to_string(), "1:5: 'radius' should be a number, found a string");
+        assert_eq!(b.count("count").unwrap_err().to_string(), "1:20: 'count' should be a whole number, found a number");
+    }
+
+    #[test]
+    fn unknown_and_repeated_keys_are_rejected() {
+        let mut b = block("s { radius = 1 radus = 2 }");
+        b.number("radius").unwrap();
+        assert_eq!(b.finish().unwrap_err().to_string(), "1:16: unknown or repeated setting 'radus'");
+
+        let mut b = block("s { radius = 1 radius = 2 }");
+        assert_eq!(b.number("radius").unwrap(), Some(1.0));
+        assert_eq!(b.finish().unwrap_err().to_string(), "1:16: unknown or repeated setting 'radius'");
+
+        let mut b = block("s { radius = 1 }");
+        b.number("radius").unwrap();
+        assert!(b.finish().is_ok());
+    }
+
+    #[test]
+    fn checked_numbers() {
+        let mut b = block("s { aspect = -2 ior = 1.5 samples = 0 }");
+
+        assert_eq!(b.positive("aspect").unwrap_err().to_string(), "1:5: 'aspect' has to be above 0");
+        assert_eq!(b.positive("ior").unwrap(), Some(1.5));
+        assert_eq!(b.positive_count("samples").unwrap_err().to_string(), "1:27: 'samples' has to be at least 1");
+        assert_eq!(b.positive_count("width").unwrap(), None);
+    }
+}
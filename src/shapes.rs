@@ -1,3 +1,8 @@
 pub mod shape;
 pub mod sphere;
-pub mod plane;
\ No newline at end of file
+pub mod plane;
+pub mod quad;
+pub mod triangle;
+pub mod mesh;
+pub mod motion;
+pub mod instance;
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
+use crate::math::matrix::Matrix4;
+use crate::util::hittable::*;
+use crate::shapes::shape::Shape;
+
+// A shape moved, turned or stretched by a transform. the shape itself is
+// shared, so the same mesh can be placed many times without copying it.
+#[derive(Clone)]
+pub struct Instance {
+    pub object: Arc<Shape>,
+    to_world: Matrix4,
+    to_object: Matrix4,
+    normal_to_world: Matrix4, // inverse transpose, keeps normals perpendicular under non-uniform scale.
+    bounds: Option<Aabb>,
+}
+
+impl Instance {
+    // None if the transform can't be undone (a scale of 0 somewhere).
+    pub fn new(object: Arc<Shape>, to_world: Matrix4) -> Option<Self> {
+        let to_object = to_world.inverse()?;
+
+        // the world box holds all eight corners of the object's box.
+        let bounds = object.bounding_box().map(|b| {
+            (0..8).fold(Aabb::empty(), |world_box, i| {
+                let corner = Point::new(
+                    if i & 1 == 0 { b.min.a } else { b.max.a },
+                    if i & 2 == 0 { b.min.b } else { b.max.b },
+                    if i & 4 == 0 { b.min.c } else { b.max.c },
+                );
+                world_box.grow(to_world.transform_point(corner))
+            })
+        });
+
+        Some(Instance { object, to_world, to_object, normal_to_world: to_object.transpose(), bounds })
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.to_world
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        // the direction isn't normalised, so distances along the ray are the
+        // same in both spaces, and so is its spread per unit of them.
+        let local = Ray {
+            origin: self.to_object.transform_point(ray.origin),
+            direction: self.to_object.transform_vector(ray.direction),
+            ..ray
+        };
+
+        let mut collision = self.object.hit(local)?;
+
+        // the normal already faces the ray in object space, transforming
+        // both keeps it that way. local_point stays in object space, so
+        // textures stick to the shape.
+        collision.hit_point = ray.at(collision.distance);
+        collision.normal = self.normal_to_world.transform_vector(collision.normal).unit();
+        collision.geometric_normal = self.normal_to_world.transform_vector(collision.geometric_normal).unit();
+
+        Some(collision)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::sphere::Sphere;
+    use crate::util::material::Material;
+    use crate::util::texture::Texture;
+
+    fn unit_sphere() -> Arc<Shape> {
+        let material = Material::Lambertian(Texture::Solid(Point::origin()));
+        Arc::new(Shape::sphere(Sphere::new_pos_t(Point::origin(), material, 1.0)))
+    }
+
+    #[test]
+    fn hits_are_in_world_space() {
+        let instance = Instance::new(unit_sphere(), Matrix4::translate(Point::new(0.0, 0.0, -3.0))).unwrap();
+        let hit = instance.hit(Ray::new(Point::origin(), Point::new(0.0, 0.0, -1.0))).unwrap();
+
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        assert!((hit.hit_point.c + 2.0).abs() < 1e-5);
+        assert!((hit.normal.c - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_uneven_scale() {
+        // x^2/4 + y^2 + z^2 = 1
+        let instance = Instance::new(unit_sphere(), Matrix4::scale(Point::new(2.0, 1.0, 1.0))).unwrap();
+        let hit = instance.hit(Ray::new(Point::new(1.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0))).unwrap();
+
+        let z = 0.75f32.sqrt();
+        assert!((hit.hit_point.c - z).abs() < 1e-4);
+
+        // the gradient of the surface, (x/4, y, z).
+        let expected = Point::new(0.25, 0.0, z).unit();
+        assert!((hit.normal + expected.scalar_mul(-1.0)).len() < 1e-4, "{}", hit.normal);
+    }
+
+    #[test]
+    fn bounds_cover_the_turned_shape() {
+        let instance = Instance::new(unit_sphere(), Matrix4::rotate_z(45.0) * Matrix4::scale(Point::new(3.0, 1.0, 1.0))).unwrap();
+        let bounds = instance.bounding_box().unwrap();
+
+        // the tip of the stretched sphere, turned 45 degrees.
+        let tip = 3.0 / 2f32.sqrt();
+        assert!(bounds.max.a >= tip && bounds.max.b >= tip);
+        assert!(bounds.min.a <= -tip && bounds.min.b <= -tip);
+    }
+
+    #[test]
+    fn flat_transforms_are_refused() {
+        assert!(Instance::new(unit_sphere(), Matrix4::scale(Point::new(0.0, 1.0, 1.0))).is_none());
+    }
+}
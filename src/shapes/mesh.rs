@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
+use crate::util::material::*;
+use crate::util::hittable::*;
+use crate::util::bvh::*;
+use crate::shapes::triangle::*;
+
+// Vertex and index buffers for a triangle mesh.
+// normals and uvs are either empty or one per position.
+// material_ids is either empty (everything uses material 0) or one per triangle.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Point>,
+    pub uvs: Vec<Point>,
+    pub indices: Vec<[usize; 3]>,
+    pub material_ids: Vec<usize>,
+    pub groups: Vec<MeshGroup>,
+}
+
+// a named run of triangles, like an obj "g" or "o" block.
+#[derive(Clone, Debug)]
+pub struct MeshGroup {
+    pub name: String,
+    pub start: usize, // first triangle
+    pub end: usize, // one past the last triangle
+}
+
+impl MeshData {
+    pub fn corners(&self, triangle: usize) -> (Point, Point, Point) {
+        let [i0, i1, i2] = self.indices[triangle];
+        (self.positions[i0], self.positions[i1], self.positions[i2])
+    }
+
+    fn attribute(values: &[Point], index: [usize; 3]) -> Option<[Point; 3]> {
+        if values.is_empty() {
+            return None;
+        }
+
+        Some([values[index[0]], values[index[1]], values[index[2]]])
+    }
+}
+
+// A triangle mesh. The buffers and the bvh over the triangles are shared,
+// so cloning a mesh (the world does this) doesn't copy the geometry.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub data: Arc<MeshData>,
+    pub materials: Vec<Material>,
+    bvh: Arc<Bvh>,
+    t_min: f32,
+    t_max: f32
+}
+
+impl Mesh {
+    pub fn new(data: MeshData, material: Material) -> Self {
+        Mesh::from_shared(Arc::new(data), vec![material])
+    }
+
+    // materials is indexed by data.material_ids.
+    pub fn from_shared(data: Arc<MeshData>, materials: Vec<Material>) -> Self {
+        let boxes: Vec<(usize, Aabb)> = (0..data.indices.len())
+            .map(|i| {
+                let (p0, p1, p2) = data.corners(i);
+                (i, triangle_bounds(p0, p1, p2))
+            })
+            .collect();
+
+        let bvh = Arc::new(Bvh::build(&boxes, SplitMethod::Sah));
+
+        Mesh { data, materials, bvh, t_min: 0.001, t_max: f32::INFINITY }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.data.indices.len()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&MeshGroup> {
+        self.data.groups.iter().find(|g| g.name == name)
+    }
+
+    fn hit_triangle(&self, triangle: usize, ray: Ray) -> Option<RayCollision> {
+        let (p0, p1, p2) = self.data.corners(triangle);
+        let (t, b1, b2) = intersect(&ray, p0, p1, p2, self.t_min, self.t_max)?;
+
+        let index = self.data.indices[triangle];
+
+        // zero normals (or ones that cancel out) have no direction, the
+        // face's own is the best guess.
+        let normal = match MeshData::attribute(&self.data.normals, index) {
+            Some(normals) => {
+                let n = interpolate(normals, b1, b2);
+                if n.len() > 1e-8 { n.unit() } else { face_normal(p0, p1, p2) }
+            },
+            None => face_normal(p0, p1, p2),
+        };
+
+        // without uvs fall back to the barycentric coordinates.
+        let uvs = MeshData::attribute(&self.data.uvs, index)
+            .unwrap_or([Point::origin(), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0)]);
+        let uv = interpolate(uvs, b1, b2);
+
+        let material = self.data.material_ids.get(triangle).copied().unwrap_or(0);
+
+        let collision = RayCollision::new_uv(ray, normal, t, uv, self.materials[material].to_owned())
+            .with_geometric_normal(face_normal(p0, p1, p2));
+        Some(collision.with_footprint(ray, uv_per_unit([p0, p1, p2], uvs)))
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        self.bvh
+            .traverse(ray, |i| self.hit_triangle(i, ray))
+            .map(|(_, c)| c)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+}
@@ -0,0 +1,62 @@
+use crate::math::lerp_vec;
+use crate::math::vector::*;
+use crate::math::aabb::Aabb;
+
+// Where something is over time: positions at increasing times, moving in a
+// straight line between them. before the first key and after the last it
+// holds still.
+#[derive(Clone, Debug)]
+pub struct Motion {
+    keys: Vec<(f32, Point)>,
+}
+
+impl Motion {
+    // from at time 0 to to at time 1.
+    pub fn linear(from: Point, to: Point) -> Self {
+        Motion { keys: vec![(0.0, from), (1.0, to)] }
+    }
+
+    // keys are sorted by time, so they can be given in any order. None if
+    // there are no keys at all.
+    pub fn keyframed(mut keys: Vec<(f32, Point)>) -> Option<Self> {
+        if keys.is_empty() {
+            return None;
+        }
+
+        keys.sort_by(|x, y| x.0.total_cmp(&y.0));
+        Some(Motion { keys })
+    }
+
+    // positions evenly spread from time 0 to 1.
+    pub fn path(points: &[Point]) -> Option<Self> {
+        let steps = points.len().saturating_sub(1).max(1) as f32;
+
+        Motion::keyframed(points.iter().enumerate().map(|(i, p)| (i as f32 / steps, *p)).collect())
+    }
+
+    pub fn at(&self, time: f32) -> Point {
+        let first = self.keys[0];
+        if time <= first.0 {
+            return first.1;
+        }
+
+        // the first key after time, and the one before it.
+        match self.keys.iter().position(|(t, _)| *t > time) {
+            Some(i) => {
+                let (t0, p0) = self.keys[i - 1];
+                let (t1, p1) = self.keys[i];
+
+                lerp_vec(p0, p1, (time - t0) / (t1 - t0))
+            },
+            None => self.keys[self.keys.len() - 1].1,
+        }
+    }
+
+    // box around every key. moving in straight lines between them never
+    // leaves it.
+    pub fn bounds(&self, radius: Point) -> Aabb {
+        self.keys.iter().fold(Aabb::empty(), |b, (_, p)| {
+            b.union(Aabb::new(*p + radius.scalar_mul(-1.0), *p + radius))
+        })
+    }
+}
@@ -1,62 +1,73 @@
 use crate::util::material::Material;
-use crate::{math::vector::*, util::hittable::RayCollision};
+use crate::math::vector::*;
 use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
 use crate::util::hittable::*;
 
+// Infinite plane through point, facing normal.
 #[derive(Clone, Debug)]
 pub struct Plane {
-    pub min: Point,
-    pub max: Point,
-    pub n: Point,
+    pub point: Point,
+    pub normal: Point,
     pub material: Material,
-    pub t_min: f32,
-    pub t_max: f32
+    tangent: Point, // u and v directions in the plane, for texturing.
+    bitangent: Point,
+    t_min: f32,
+    t_max: f32
 }
 
 impl Plane {
-    pub fn new (min: Point, max: Point, n: Point, material: Material) -> Plane {
-        let t_max = 0.001;
-        let t_min = f32::INFINITY;
+    pub fn new(point: Point, normal: Point, material: Material) -> Plane {
+        let normal = normal.unit();
+        let (tangent, bitangent) = normal.tangent_frame();
 
-        Plane { min, max, n, material, t_min, t_max }
+        Plane { point, normal, material, tangent, bitangent, t_min: 0.001, t_max: f32::INFINITY }
     }
 }
 
 impl Hittable for Plane {
     fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        let t_den = Vector3::dot(&ray.direction, &self.normal);
 
-        let t = {
-            let t_num = Vector3::dot(&self.min, &self.n) - Vector3::dot(&ray.origin, &self.n);
-            let t_den = Vector3::dot(&ray.direction, &self.n);
-
-            t_num / t_den
-        };  
+        // ray runs along the plane.
+        if t_den.abs() < 1e-8 {
+            return None;
+        }
 
+        let t = Vector3::dot(&(self.point + ray.origin.scalar_mul(-1.0)), &self.normal) / t_den;
         if t < self.t_min || t > self.t_max {
-            return None
+            return None;
         }
 
-        // auto x = r.origin().x() + t*r.direction().x();
-        // auto y = r.origin().y() + t*r.direction().y();    
+        // one uv unit per unit along the plane, the texture's wrap mode
+        // decides what happens past the first.
+        let offset = ray.at(t) + self.point.scalar_mul(-1.0);
+        let u = Vector3::dot(&offset, &self.tangent);
+        let v = Vector3::dot(&offset, &self.bitangent);
 
-        // let x = ray.origin.a + t * ray.direction.a;
-        // let y = ray.origin.b + t * ray.direction.b;
-        // let z = ray.origin.c + t * ray.direction.c;
-
-        // print!("Ray with t: {:?}\n", ray);
+        Some(RayCollision::new_uv(ray, self.normal, t, Point::new(u, v, 0.0), self.material.to_owned()).with_footprint(ray, 1.0))
+    }
 
-        // if x < self.p1.a || x > self.p2.a {
-        //     return None;
-        // }
+    fn bounding_box(&self) -> Option<Aabb> {
+        // goes on forever.
+        None
+    }
+}
 
-        // if y < self.p1.b || y > self.p2.b {
-        //     return None;
-        // }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::texture::Texture;
 
-        // if z < self.p1.c || z > self.p2.c {
-        //     return None;
-        // }
+    #[test]
+    fn uvs_count_units_without_wrapping() {
+        let plane = Plane::new(Point::origin(), Point::new(0.0, 1.0, 0.0), Material::Lambertian(Texture::Solid(Point::origin())));
+        let hit = plane.hit(Ray::new(Point::new(2.5, 1.0, -1.5), Point::new(0.0, -1.0, 0.0))).unwrap();
 
-        Some(RayCollision::new(ray, self.n, t, self.material.to_owned()))
+        // tangents are unit length, so the uv is as far from the origin as
+        // the hit is, past the first tile.
+        let (u, v) = (hit.uv.a, hit.uv.b);
+        assert!(((u * u + v * v).sqrt() - (2.5f32 * 2.5 + 1.5 * 1.5).sqrt()).abs() < 1e-5);
+        assert!(u.abs() > 1.0 || v.abs() > 1.0);
     }
-}
\ No newline at end of file
+}
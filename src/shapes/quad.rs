@@ -0,0 +1,93 @@
+use crate::util::material::Material;
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
+use crate::util::hittable::*;
+
+// Parallelogram with one corner at origin and sides u and v. the normal is
+// u x v, and uv coordinates run 0 to 1 along u and v.
+#[derive(Clone, Debug)]
+pub struct Quad {
+    pub origin: Point,
+    pub u: Point,
+    pub v: Point,
+    pub material: Material,
+    normal: Point,
+    w: Point, // n / (n . n), turns a point in the plane into (alpha, beta).
+    t_min: f32,
+    t_max: f32
+}
+
+impl Quad {
+    pub fn new(origin: Point, u: Point, v: Point, material: Material) -> Self {
+        let n = u.cross(v);
+        let w = n.scalar_div(Vector3::dot(&n, &n));
+
+        Quad { origin, u, v, material, normal: n.unit(), w, t_min: 0.001, t_max: f32::INFINITY }
+    }
+
+    // axis-aligned rectangle between two opposite corners. one axis has to be
+    // flat; the normal points along it, towards positive.
+    pub fn rect(min: Point, max: Point, material: Material) -> Option<Self> {
+        let d = max + min.scalar_mul(-1.0);
+
+        let (u, v) = if d.a == 0.0 {
+            (Point::new(0.0, d.b, 0.0), Point::new(0.0, 0.0, d.c))
+        } else if d.b == 0.0 {
+            (Point::new(0.0, 0.0, d.c), Point::new(d.a, 0.0, 0.0))
+        } else if d.c == 0.0 {
+            (Point::new(d.a, 0.0, 0.0), Point::new(0.0, d.b, 0.0))
+        } else {
+            return None;
+        };
+
+        Some(Quad::new(min, u, v, material))
+    }
+
+    pub fn normal(&self) -> Point {
+        self.normal
+    }
+
+    pub fn area(&self) -> f32 {
+        self.u.cross(self.v).len()
+    }
+
+    // false when u and v are 0 or parallel, which leaves no normal.
+    pub fn has_area(&self) -> bool {
+        let area = self.area();
+        area > 0.0 && area.is_finite()
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        let t_den = Vector3::dot(&ray.direction, &self.normal);
+
+        // ray runs along the quad.
+        if t_den.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vector3::dot(&(self.origin + ray.origin.scalar_mul(-1.0)), &self.normal) / t_den;
+        if t < self.t_min || t > self.t_max {
+            return None;
+        }
+
+        // where the hit lands in the quad's own u, v coordinates.
+        let p = ray.at(t) + self.origin.scalar_mul(-1.0);
+        let alpha = Vector3::dot(&self.w, &p.cross(self.v));
+        let beta = Vector3::dot(&self.w, &self.u.cross(p));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let collision = RayCollision::new_uv(ray, self.normal, t, Point::new(alpha, beta, 0.0), self.material.to_owned());
+        Some(collision.with_footprint(ray, 1.0 / self.area().sqrt()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let o = self.origin;
+        Some(Aabb::new(o, o).grow(o + self.u).grow(o + self.v).grow(o + self.u + self.v))
+    }
+}
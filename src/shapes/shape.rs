@@ -1,15 +1,22 @@
-// use crate::shapes::plane::*;
 use crate::shapes::sphere::*;
 use crate::util::hittable::Hittable;
+use crate::math::aabb::Aabb;
 
 use super::plane::Plane;
+use super::quad::Quad;
+use super::triangle::Triangle;
+use super::mesh::Mesh;
+use super::instance::Instance;
 
 // Wrapper shape type so the world has no need for dyn Hittable
-// TODO Implement Plane shape.
 #[derive(Clone)]
 pub enum Shape {
     Sphere(Sphere),
-    Plane(Plane)
+    Plane(Plane),
+    Quad(Quad),
+    Triangle(Triangle),
+    Mesh(Mesh),
+    Instance(Instance)
 }
 
 impl Shape {
@@ -20,13 +27,44 @@ impl Shape {
     pub fn plane(obj: Plane) -> Self {
         Shape::Plane(obj)
     }
+
+    pub fn quad(obj: Quad) -> Self {
+        Shape::Quad(obj)
+    }
+
+    pub fn triangle(obj: Triangle) -> Self {
+        Shape::Triangle(obj)
+    }
+
+    pub fn mesh(obj: Mesh) -> Self {
+        Shape::Mesh(obj)
+    }
+
+    pub fn instance(obj: Instance) -> Self {
+        Shape::Instance(obj)
+    }
 }
 
 impl Hittable for Shape {
     fn hit(&self, ray: crate::math::ray::Ray) -> Option<crate::util::hittable::RayCollision> {
         match self {
             Shape::Sphere(o) => o.hit(ray),
-            Shape::Plane(o) => o.hit(ray)
+            Shape::Plane(o) => o.hit(ray),
+            Shape::Quad(o) => o.hit(ray),
+            Shape::Triangle(o) => o.hit(ray),
+            Shape::Mesh(o) => o.hit(ray),
+            Shape::Instance(o) => o.hit(ray)
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self {
+            Shape::Sphere(o) => o.bounding_box(),
+            Shape::Plane(o) => o.bounding_box(),
+            Shape::Quad(o) => o.bounding_box(),
+            Shape::Triangle(o) => o.bounding_box(),
+            Shape::Mesh(o) => o.bounding_box(),
+            Shape::Instance(o) => o.bounding_box()
         }
     }
-}
\ No newline at end of file
+}
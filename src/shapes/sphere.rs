@@ -1,5 +1,7 @@
 use crate::math::vector::*;
 use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
+use crate::shapes::motion::Motion;
 use crate::util::material::*;
 use crate::util::hittable::*;
 
@@ -8,13 +10,14 @@ pub struct Sphere {
     pub center: Point,
     pub radius: f32,
     pub material: Material,
+    pub motion: Option<Motion>, // replaces center for moving spheres.
     t_min: f32,
     t_max: f32
 }
 
 impl Sphere {
     pub fn new(center: Point, radius: f32, material: Material, t_min: f32, t_max: f32) -> Self {
-        Sphere { center, radius, material, t_min, t_max }
+        Sphere { center, radius, material, motion: None, t_min, t_max }
     }
 
     pub fn new_pos_t(center: Point, material: Material, radius: f32) -> Self {
@@ -23,7 +26,21 @@ impl Sphere {
         let t_min: f32 = 0.001; 
         let t_max: f32 = f32::INFINITY;
 
-        Sphere { center, radius, material, t_min, t_max }
+        Sphere { center, radius, material, motion: None, t_min, t_max }
+    }
+
+    // a sphere that follows motion, starting where it is at time 0.
+    pub fn moving(motion: Motion, material: Material, radius: f32) -> Self {
+        let mut sphere = Sphere::new_pos_t(motion.at(0.0), material, radius);
+        sphere.motion = Some(motion);
+        sphere
+    }
+
+    pub fn center_at(&self, time: f32) -> Point {
+        match &self.motion {
+            Some(motion) => motion.at(time),
+            None => self.center,
+        }
     }
 }
 
@@ -31,7 +48,8 @@ impl Hittable for Sphere {
     fn hit(&self, ray: Ray) -> Option<RayCollision> {
         // t^2*b*b + 2tb*(A-C)+(A-C)*(A-C) t solved using quadratic formula
         // optimized to t = -h +- sqrt(h^2 - ac) all over a
-        let o_min_c: Point = ray.origin + self.center.scalar_mul(-1.0);
+        let center: Point = self.center_at(ray.time);
+        let o_min_c: Point = ray.origin + center.scalar_mul(-1.0);
 
         let a: f32 = Vector3::dot(&ray.direction, &ray.direction);
         let half_b: f32 = Vector3::dot(&ray.direction, &o_min_c);
@@ -42,15 +60,73 @@ impl Hittable for Sphere {
             return None;
         }
 
-        // finish quad formula to get ray distance. 
+        // finish quad formula to get ray distance. the near root first, the
+        // far one when the ray starts inside the sphere (leaving glass).
+        let sqrt_d: f32 = discriminant.sqrt();
+        let in_range = |t: f32| t >= self.t_min && t <= self.t_max;
 
-        let t_root: f32 = (-1.0 * half_b - discriminant.sqrt()) / a;
-        if t_root < self.t_min || t_root > self.t_max {
-            return None
+        let mut t_root: f32 = (-half_b - sqrt_d) / a;
+        if !in_range(t_root) {
+            t_root = (-half_b + sqrt_d) / a;
+            if !in_range(t_root) {
+                return None;
+            }
         }
 
-        let norm: Point = (ray.at(t_root) + self.center.scalar_mul(-1.0)).scalar_div(self.radius); 
+        let norm: Point = (ray.at(t_root) + center.scalar_mul(-1.0)).scalar_div(self.radius); 
         
-        Some(RayCollision::new(ray, norm, t_root, self.material.to_owned())) 
+        // u runs around the equator, v pole to pole, this is in between.
+        let uv_per_unit = 1.0 / (std::f32::consts::SQRT_2 * std::f32::consts::PI * self.radius);
+
+        let mut collision = RayCollision::new(ray, norm, t_root, self.material.to_owned());
+        // where the point was at time 0, so textures move with the sphere.
+        collision.local_point = collision.hit_point + (self.center + center.scalar_mul(-1.0));
+
+        Some(collision.with_footprint(ray, uv_per_unit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Point::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+
+        match &self.motion {
+            Some(motion) => Some(motion.bounds(r)),
+            None => Some(Aabb::new(self.center + r.scalar_mul(-1.0), self.center + r)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::texture::Texture;
+
+    fn sphere() -> Sphere {
+        Sphere::new_pos_t(Point::origin(), Material::Lambertian(Texture::Solid(Point::origin())), 2.0)
+    }
+
+    #[test]
+    fn hits_the_near_side_from_outside() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0));
+        let hit = sphere().hit(ray).unwrap();
+
+        assert!((hit.distance - 3.0).abs() < 1e-5);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn hits_the_exit_from_inside() {
+        let ray = Ray::new(Point::origin(), Point::new(0.0, 1.0, 0.0));
+        let hit = sphere().hit(ray).unwrap();
+
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        assert!(!hit.front_face);
+        // the normal faces back against the ray.
+        assert!(hit.normal.b < 0.0);
+    }
+
+    #[test]
+    fn misses_behind_the_ray() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, 1.0));
+        assert!(sphere().hit(ray).is_none());
     }
 }
@@ -0,0 +1,115 @@
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
+use crate::util::material::*;
+use crate::util::hittable::*;
+
+#[derive(Clone, Debug)]
+pub struct Triangle {
+    pub vertices: [Point; 3],
+    pub normals: Option<[Point; 3]>, // per-vertex normals, face normal if None.
+    pub uvs: [Point; 3],
+    pub material: Material,
+    t_min: f32,
+    t_max: f32
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point; 3], material: Material) -> Self {
+        // uvs follow the barycentric coordinates by default.
+        let uvs = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+
+        Triangle { vertices, normals: None, uvs, material, t_min: 0.001, t_max: f32::INFINITY }
+    }
+
+    pub fn new_shaded(vertices: [Point; 3], normals: [Point; 3], uvs: [Point; 3], material: Material) -> Self {
+        Triangle { vertices, normals: Some(normals), uvs, material, t_min: 0.001, t_max: f32::INFINITY }
+    }
+}
+
+// Möller–Trumbore ray/triangle intersection.
+// returns (t, b1, b2) where b1, b2 are the barycentric weights of p1 and p2,
+// so the hit point is (1 - b1 - b2) * p0 + b1 * p1 + b2 * p2.
+pub fn intersect(ray: &Ray, p0: Point, p1: Point, p2: Point, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = p1 + p0.scalar_mul(-1.0);
+    let edge2 = p2 + p0.scalar_mul(-1.0);
+
+    let p = ray.direction.cross(edge2);
+    let det = Vector3::dot(&edge1, &p);
+
+    // ray is parallel to the triangle.
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin + p0.scalar_mul(-1.0);
+    let b1 = Vector3::dot(&s, &p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let b2 = Vector3::dot(&ray.direction, &q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = Vector3::dot(&edge2, &q) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+// weighted sum of per-vertex attributes (normals, uvs) at a barycentric hit.
+pub fn interpolate(values: [Point; 3], b1: f32, b2: f32) -> Point {
+    values[0].scalar_mul(1.0 - b1 - b2)
+        + values[1].scalar_mul(b1)
+        + values[2].scalar_mul(b2)
+}
+
+// how many uv units the triangle fits in one unit of its surface, the
+// square root of its area in uv over its area in space.
+pub fn uv_per_unit(corners: [Point; 3], uvs: [Point; 3]) -> f32 {
+    let [p0, p1, p2] = corners;
+    let area = (p1 + p0.scalar_mul(-1.0)).cross(p2 + p0.scalar_mul(-1.0)).len();
+    let uv_area = (uvs[1] + uvs[0].scalar_mul(-1.0)).cross(uvs[2] + uvs[0].scalar_mul(-1.0)).len();
+
+    if area > 0.0 { (uv_area / area).sqrt() } else { 0.0 }
+}
+
+pub fn face_normal(p0: Point, p1: Point, p2: Point) -> Point {
+    (p1 + p0.scalar_mul(-1.0)).cross(p2 + p0.scalar_mul(-1.0)).unit()
+}
+
+pub fn triangle_bounds(p0: Point, p1: Point, p2: Point) -> Aabb {
+    Aabb::new(p0, p0).grow(p1).grow(p2)
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        let [p0, p1, p2] = self.vertices;
+        let (t, b1, b2) = intersect(&ray, p0, p1, p2, self.t_min, self.t_max)?;
+
+        let normal = match self.normals {
+            Some(normals) => interpolate(normals, b1, b2).unit(),
+            None => face_normal(p0, p1, p2),
+        };
+        let uv = interpolate(self.uvs, b1, b2);
+
+        let collision = RayCollision::new_uv(ray, normal, t, uv, self.material.to_owned())
+            .with_geometric_normal(face_normal(p0, p1, p2));
+        Some(collision.with_footprint(ray, uv_per_unit(self.vertices, self.uvs)))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = self.vertices;
+        Some(triangle_bounds(p0, p1, p2))
+    }
+}
@@ -2,4 +2,14 @@ pub mod image;
 pub mod camera;
 pub mod hittable;
 pub mod material;
+pub mod pbr;
 pub mod texture;
+pub mod noise;
+pub mod bvh;
+pub mod obj;
+pub mod framebuffer;
+pub mod output;
+pub mod environment;
+pub mod light;
+pub mod integrator;
+pub mod sampler;
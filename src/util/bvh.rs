@@ -0,0 +1,250 @@
+use crate::math::aabb::*;
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::util::hittable::RayCollision;
+
+// how to pick the split plane when building a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SplitMethod {
+    Midpoint, // split the centroid bounds in half along the longest axis.
+    Sah,      // binned surface area heuristic. slower to build, faster to trace.
+}
+
+const MAX_LEAF_SIZE: usize = 4;
+const SAH_BUCKETS: usize = 12;
+
+#[derive(Clone, Debug)]
+enum BvhNode {
+    Leaf { bounds: Aabb, first: usize, count: usize },
+    Interior { bounds: Aabb, left: usize, right: usize, axis: usize },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Flattened bounding volume hierarchy over a list of primitive boxes.
+// It only knows about indices, so anything with a list of boxes
+// (the world, a mesh, ...) can use it.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>, // primitive indices, leaves point into ranges of this.
+}
+
+impl Bvh {
+    pub fn build(boxes: &[(usize, Aabb)], method: SplitMethod) -> Self {
+        let mut bvh = Bvh { nodes: vec![], indices: vec![] };
+
+        if boxes.is_empty() {
+            return bvh;
+        }
+
+        let mut prims: Vec<(usize, Aabb, Point)> = boxes
+            .iter()
+            .map(|(i, b)| (*i, *b, b.centroid()))
+            .collect();
+
+        bvh.build_range(&mut prims, 0, method);
+        bvh.indices = prims.iter().map(|p| p.0).collect();
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|n| *n.bounds())
+    }
+
+    // builds the node for prims and returns its index in self.nodes.
+    // prims is reordered in place so every leaf covers a contiguous range,
+    // offset is where prims starts in the full list.
+    fn build_range(&mut self, prims: &mut [(usize, Aabb, Point)], offset: usize, method: SplitMethod) -> usize {
+        let bounds = prims.iter().fold(Aabb::empty(), |acc, p| acc.union(p.1));
+        let node_index = self.nodes.len();
+
+        if prims.len() <= MAX_LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf { bounds, first: offset, count: prims.len() });
+            return node_index;
+        }
+
+        let centroid_bounds = prims.iter().fold(Aabb::empty(), |acc, p| acc.grow(p.2));
+        let axis_index = centroid_bounds.longest_axis();
+
+        // every centroid in the same spot, no split will separate them.
+        if axis(&centroid_bounds.extent(), axis_index) <= 0.0 {
+            self.nodes.push(BvhNode::Leaf { bounds, first: offset, count: prims.len() });
+            return node_index;
+        }
+
+        let mid = match method {
+            SplitMethod::Midpoint => Bvh::split_midpoint(prims, &centroid_bounds, axis_index),
+            SplitMethod::Sah => match Bvh::split_sah(prims, &bounds, &centroid_bounds, axis_index) {
+                Some(mid) => mid,
+                None => {
+                    self.nodes.push(BvhNode::Leaf { bounds, first: offset, count: prims.len() });
+                    return node_index;
+                }
+            },
+        };
+
+        // reserve this node, children are pushed after it.
+        self.nodes.push(BvhNode::Leaf { bounds, first: offset, count: 0 });
+
+        let (lower, upper) = prims.split_at_mut(mid);
+        let left = self.build_range(lower, offset, method);
+        let right = self.build_range(upper, offset + mid, method);
+
+        self.nodes[node_index] = BvhNode::Interior { bounds, left, right, axis: axis_index };
+        node_index
+    }
+
+    fn split_midpoint(prims: &mut [(usize, Aabb, Point)], centroid_bounds: &Aabb, axis_index: usize) -> usize {
+        let pivot = axis(&centroid_bounds.centroid(), axis_index);
+        let mid = partition(prims, |p| axis(&p.2, axis_index) < pivot);
+
+        // all on one side, fall back to an even split by centroid order.
+        if mid == 0 || mid == prims.len() {
+            let mid = prims.len() / 2;
+            prims.select_nth_unstable_by(mid, |x, y| {
+                axis(&x.2, axis_index).total_cmp(&axis(&y.2, axis_index))
+            });
+            return mid;
+        }
+
+        mid
+    }
+
+    // returns None if keeping everything in one leaf is cheaper than any split.
+    fn split_sah(prims: &mut [(usize, Aabb, Point)], bounds: &Aabb, centroid_bounds: &Aabb, axis_index: usize) -> Option<usize> {
+        let min = axis(&centroid_bounds.min, axis_index);
+        let extent = axis(&centroid_bounds.extent(), axis_index);
+
+        let bucket_of = |p: &(usize, Aabb, Point)| -> usize {
+            let b = (SAH_BUCKETS as f32 * (axis(&p.2, axis_index) - min) / extent) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut boxes = [Aabb::empty(); SAH_BUCKETS];
+        for p in prims.iter() {
+            let b = bucket_of(p);
+            counts[b] += 1;
+            boxes[b] = boxes[b].union(p.1);
+        }
+
+        // cost of splitting after bucket i, relative to the parent area.
+        let mut best: Option<(usize, f32)> = None;
+        for i in 0..SAH_BUCKETS - 1 {
+            let (mut left_box, mut left_count) = (Aabb::empty(), 0);
+            let (mut right_box, mut right_count) = (Aabb::empty(), 0);
+
+            for j in 0..=i {
+                left_box = left_box.union(boxes[j]);
+                left_count += counts[j];
+            }
+            for j in i + 1..SAH_BUCKETS {
+                right_box = right_box.union(boxes[j]);
+                right_count += counts[j];
+            }
+
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+
+            let cost = 0.125 + (left_count as f32 * left_box.surface_area()
+                + right_count as f32 * right_box.surface_area()) / bounds.surface_area();
+
+            if best.is_none_or(|(_, c)| cost < c) {
+                best = Some((i, cost));
+            }
+        }
+
+        let (bucket, cost) = best?;
+        if prims.len() <= MAX_LEAF_SIZE * 4 && cost >= prims.len() as f32 {
+            return None;
+        }
+
+        Some(partition(prims, |p| bucket_of(p) <= bucket))
+    }
+
+    // walks the tree front to back and returns the closest hit reported by
+    // hit_prim. ties on distance go to the lower primitive index, which is
+    // what a linear scan in insertion order would return.
+    pub fn traverse<F>(&self, ray: Ray, mut hit_prim: F) -> Option<(usize, RayCollision)>
+    where
+        F: FnMut(usize) -> Option<RayCollision>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_dir = Point::new(1.0 / ray.direction.a, 1.0 / ray.direction.b, 1.0 / ray.direction.c);
+        let dir_negative = [inv_dir.a < 0.0, inv_dir.b < 0.0, inv_dir.c < 0.0];
+
+        let mut closest: Option<(usize, RayCollision)> = None;
+        let mut stack: Vec<usize> = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let t_max = closest.as_ref().map_or(f32::INFINITY, |c| c.1.distance);
+
+            if node.bounds().hit(&ray, &inv_dir, t_max).is_none() {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for &prim in &self.indices[*first..*first + *count] {
+                        if let Some(c) = hit_prim(prim) {
+                            let better = match &closest {
+                                None => true,
+                                Some((best_prim, best)) => c.distance < best.distance
+                                    || (c.distance == best.distance && prim < *best_prim),
+                            };
+
+                            if better {
+                                closest = Some((prim, c));
+                            }
+                        }
+                    }
+                },
+                BvhNode::Interior { left, right, axis, .. } => {
+                    // push the far child first so the near one is popped next.
+                    if dir_negative[*axis] {
+                        stack.push(*left);
+                        stack.push(*right);
+                    } else {
+                        stack.push(*right);
+                        stack.push(*left);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+// moves everything matching pred to the front, returns how many matched.
+fn partition<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
@@ -1,9 +1,63 @@
+use std::f32::consts::PI;
+use std::ops::Range;
+
 use crate::util::image::Image;
 
 use crate::math::*;
+use crate::math::sampling;
 use crate::math::vector::*;
 use crate::math::ray::Ray;
 use crate::util::image::*;
+use crate::util::integrator::Integrator;
+use crate::util::sampler::{Sampler, SamplerKind};
+
+// where the lens is focused.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Focus {
+    Distance(f32), // distance from the camera along the view direction.
+    Target, // autofocus on the camera's to point.
+}
+
+// Thin lens. an aperture of 0 is a pinhole, everything is sharp.
+#[derive(Copy, Clone, Debug)]
+pub struct Lens {
+    pub aperture: f32, // lens radius.
+    pub focus: Focus,
+    pub blades: u32, // aperture polygon sides, below 3 is a round aperture.
+    pub blade_rotation: f32, // degrees.
+}
+
+impl Lens {
+    pub fn pinhole() -> Self {
+        Lens { aperture: 0.0, focus: Focus::Target, blades: 0, blade_rotation: 0.0 }
+    }
+
+    // point on the aperture for the sampler's next numbers, relative to its
+    // center. bokeh takes the aperture's shape.
+    pub fn sample(&self, sampler: &mut SamplerKind) -> (f32, f32) {
+        if self.aperture <= 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let (x, y) = if self.blades < 3 {
+            sampling::uniform_disk(sampler.next_2d())
+        } else {
+            // pick one of the polygon's triangles (center, corner k, corner k+1),
+            // then a uniform point inside it.
+            let n = self.blades as f32;
+            let k = ((sampler.next_1d() * n) as u32).min(self.blades - 1) as f32;
+            let offset = self.blade_rotation.to_radians();
+            let a0 = offset + 2.0 * PI * k / n;
+            let a1 = offset + 2.0 * PI * (k + 1.0) / n;
+
+            let (s, t) = sampling::uniform_triangle(sampler.next_2d());
+
+            (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+        };
+
+        (x * self.aperture, y * self.aperture)
+    }
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Camera {
@@ -12,10 +66,19 @@ pub struct Camera {
     pub at: Point, // origin and lookfrom
     pub ll_corner: Point,
     pub right: Point,
+    pub lens: Lens,
+    pub shutter: (f32, f32), // open and close time, rays are spread across it.
+    spread: f32, // angle one pixel covers, for texture filtering.
+    u: Point, // unit right and up, for moving ray origins across the lens.
+    v: Point,
 }
 
 impl Camera {
     pub fn new(up: Point, at: Point, to: Point, image: Image) -> Self {
+        Camera::new_lens(up, at, to, image, Lens::pinhole())
+    }
+
+    pub fn new_lens(up: Point, at: Point, to: Point, image: Image, lens: Lens) -> Self {
         // vector up is inferred to be pointing from bottom -> top of view. 
         let aspect_ratio: f32 = image.width as f32 / image.height as f32;
         let theta = deg_to_rad(image.fov);
@@ -35,68 +98,69 @@ impl Camera {
         // there is a floating point errors in Mat3b3::roll... probably
         // let right_dir = Mat3b3::roll(up, deg_to_rad(270.0));
 
-        let right: Point = u.scalar_mul(view_width);
-        let up = v.scalar_mul(view_height); 
+        // the view plane sits at the focus distance, that's where the lens is sharp.
+        let focus_distance = match lens.focus {
+            Focus::Distance(d) => d,
+            Focus::Target => (at + to.scalar_mul(-1.0)).len(),
+        };
+
+        let right: Point = u.scalar_mul(view_width * focus_distance);
+        let up = v.scalar_mul(view_height * focus_distance); 
 
-        println!("Camera UP: {};\nCamera RIGHT: {}", up.to_string(), right.to_string());
-        println!("Camera AT: {};\nCamera TO: {}", at.to_string(), to.to_string());
+        println!("Camera UP: {};\nCamera RIGHT: {}", up, right);
+        println!("Camera AT: {};\nCamera TO: {}", at, to);
 
         let ll_corner: Point = at 
             + right.scalar_mul(-0.5) 
             + up.scalar_mul(-0.5) 
-            + w.scalar_mul(-1.0);
+            + w.scalar_mul(-focus_distance);
 
-        println!("Camera LL Corner: {}", ll_corner.to_string());
+        println!("Camera LL Corner: {}", ll_corner);
 
-        Camera { up, to, at, ll_corner, right}
+        let spread = view_height / image.height as f32;
+
+        Camera { up, to, at, ll_corner, right, lens, shutter: (0.0, 1.0), spread, u, v }
     }
 
-    pub fn get_ray(self, u: f32, v: f32) -> Ray {
-        let dir: Point = self.ll_corner + self.right.scalar_mul(u) + self.up.scalar_mul(v) + self.at.scalar_mul(-1.0);
+    pub fn get_ray(self, u: f32, v: f32, sampler: &mut SamplerKind) -> Ray {
+        // rays leave from somewhere on the lens, but all meet again on the focus plane.
+        let (x, y) = self.lens.sample(sampler);
+        let origin: Point = self.at + self.u.scalar_mul(x) + self.v.scalar_mul(y);
+
+        let dir: Point = self.ll_corner + self.right.scalar_mul(u) + self.up.scalar_mul(v) + origin.scalar_mul(-1.0);
+
+        // moving shapes blur across the time the shutter is open.
+        let (open, close) = self.shutter;
+        let time = if close > open { lerp(open, close, sampler.next_1d()) } else { open };
 
-        Ray::new(self.at, dir)
+        Ray { spread: self.spread, ..Ray::new_at(origin, dir, time) }
     }
 
-    // returns the average color found by sampling around a pixel
-    pub fn sample_pixel(&self, context: &RenderObject) -> Color {
-        // let pixel = Arc::new(Mutex::new(Color::origin()));
+    // returns the sum of the colors found by samples of a pixel (a pass's
+    // share of its samples per pixel), for the caller to average.
+    pub fn sample_pixel(&self, context: &RenderObject, samples: Range<u32>) -> Color {
         let mut pixel = Color::origin();
-        // let mut samples: Vec<JoinHandle<Ray>> = vec![];
+        let mut sampler = context.sampler;
 
-        let sample_offset = |original: f32, length: f32| -> f32 {
-            let numerator: f32 = original + random_f32(0.0, 1.0);
+        let sample_offset = |original: f32, jitter: f32, length: f32| -> f32 {
+            let numerator: f32 = original + jitter;
             numerator / (length - 1.0)
         };
 
-        // let mut handles = vec![];
-        for _ in 0..context.image.samples_per_pixel {
-            // faster without using threads... for some reason :/
-            // maybe I am simply using threads wrong
-            let u: f32 = sample_offset(context.coordinate.a, context.image.width as f32);
-            let v: f32 = sample_offset(context.coordinate.b, context.image.height as f32);
-
-            let world = context.world.to_owned();
-            let camera = context.camera.to_owned();
-            // handles.push(thread::spawn(move || {
-            let ray = camera.get_ray(u, v);
-            pixel = pixel + ray.ray_color(&world, 20);
-            // }));
+        let (x, y) = (context.coordinate.a as u32, context.coordinate.b as u32);
+
+        // samples run on this thread, Image::render parallelises over tiles.
+        for index in samples {
+            sampler.start(x, y, index);
+
+            let (jitter_u, jitter_v) = sampler.next_2d();
+            let u: f32 = sample_offset(context.coordinate.a, jitter_u, context.image.width as f32);
+            let v: f32 = sample_offset(context.coordinate.b, jitter_v, context.image.height as f32);
+
+            let ray = context.camera.get_ray(u, v, &mut sampler);
+            pixel = pixel + context.image.integrator.li(ray, context.world, context.image.max_depth, &mut sampler);
         }
 
-        // for handle in handles {
-        //     pixel = pixel + handle.join().unwrap();
-        // }
-
-        let scale: f32 = 1.0 / context.image.samples_per_pixel as f32;
-        let color = pixel.scalar_mul(scale);
-        // let color: Color = pixel.lock().unwrap().scalar_mul(scale);
-
-        // sqrt is gamma 2 color correction:
-        // color ^ (1/gamma)
-        Color::new(
-            clamp(color.a.sqrt(), 0.0, 0.999),
-            clamp(color.b.sqrt(), 0.0, 0.999),
-            clamp(color.c.sqrt(), 0.0, 0.999)
-        )
+        pixel
     }
 }
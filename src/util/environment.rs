@@ -0,0 +1,49 @@
+use std::f32::consts::PI;
+
+use crate::math::*;
+use crate::math::vector::*;
+use crate::util::texture::Texture;
+
+// What a ray sees when it leaves the scene without hitting anything.
+#[derive(Clone, Debug)]
+pub enum Environment {
+    Constant(Color),
+    Gradient { bottom: Color, top: Color }, // blends from straight down to straight up.
+    Map { texture: Texture, rotation: f32, intensity: f32 }, // equirectangular image, rotation in degrees about the up axis.
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::sky()
+    }
+}
+
+impl Environment {
+    // the original white to blue sky.
+    pub fn sky() -> Self {
+        Environment::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn color(&self, direction: Point) -> Color {
+        let unit_dir = direction.unit();
+
+        match self {
+            Environment::Constant(c) => *c,
+            Environment::Gradient { bottom, top } => {
+                let t: f32 = 0.5 * (unit_dir.b + 1.0);
+                lerp_vec(*bottom, *top, t)
+            },
+            Environment::Map { texture, rotation, intensity } => {
+                // longitude around the up (b) axis, latitude from straight down.
+                let u = (unit_dir.c.atan2(unit_dir.a) / (2.0 * PI) + 0.5 + rotation / 360.0).rem_euclid(1.0);
+                let v = (-unit_dir.b).clamp(-1.0, 1.0).acos() / PI;
+
+                // procedural textures see the sky as a unit sphere.
+                texture.get(&u, &v, &unit_dir, 0.0).scalar_mul(*intensity)
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::math::clamp;
+use crate::math::vector::*;
+
+// Linear, unclamped pixel colors, top row first. Gamma and clamping only
+// happen when writing 8-bit formats, so hdr output keeps the real values.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer { width, height, pixels: vec![Color::origin(); (width * height) as usize] }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    // gamma 2 corrected, clamped 8-bit rgb, for png/jpeg/ppm.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        // sqrt is gamma 2 color correction:
+        // color ^ (1/gamma)
+        let encode = |x: f32| -> u8 { (clamp(x.max(0.0).sqrt(), 0.0, 0.999) * 255.0) as u8 };
+
+        self.pixels
+            .iter()
+            .flat_map(|c| [encode(c.a), encode(c.b), encode(c.c)])
+            .collect()
+    }
+
+    // raw linear values for exr/hdr.
+    pub fn to_rgb32f(&self) -> Vec<f32> {
+        self.pixels
+            .iter()
+            .flat_map(|c| [c.a, c.b, c.c])
+            .collect()
+    }
+}
+
+// Running sums of samples, for rendering in passes. Every pixel keeps its
+// own count, so a render stopped part way through a pass still averages
+// each pixel correctly.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    sums: Vec<Color>,
+    samples: Vec<u32>,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Accumulator { width, height, sums: vec![Color::origin(); size], samples: vec![0; size] }
+    }
+
+    // sum is samples pixel samples added together.
+    pub fn add(&mut self, index: usize, sum: Color, samples: u32) {
+        self.sums[index] = self.sums[index] + sum;
+        self.samples[index] += samples;
+    }
+
+    // the average so far. pixels without samples yet are black.
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let pixels = self.sums
+            .iter()
+            .zip(&self.samples)
+            .map(|(&sum, &n)| if n == 0 { Color::origin() } else { sum.scalar_mul(1.0 / n as f32) })
+            .collect();
+
+        Framebuffer { width: self.width, height: self.height, pixels }
+    }
+}
@@ -4,13 +4,21 @@ use std::f32::consts::PI;
 
 use crate::math::vector::*;
 use crate::math::ray::Ray;
+use crate::math::aabb::Aabb;
 use crate::util::material::*;
+use crate::util::bvh::*;
+use crate::util::environment::Environment;
+use crate::util::light::{Light, LightSample};
+use crate::util::sampler::{Sampler, SamplerKind};
 
 use crate::shapes::shape::*;
 
 // Trait for render-able objects in the world.
 pub trait Hittable {
     fn hit(&self, ray: Ray) -> Option<RayCollision>;
+
+    // None for shapes that go on forever (like an infinite plane).
+    fn bounding_box(&self) -> Option<Aabb>;
 }
 
 pub trait Collision {}
@@ -18,15 +26,30 @@ pub trait Collision {}
 #[derive(Clone, Debug)]
 pub struct RayCollision { // returned when an object is hit by a ray.
     pub hit_point: Point, // actual point of collision.
+    pub local_point: Point, // the same point on the shape before it was placed or moved, for solid textures.
     pub normal: Point, // collided surface's normal from hit_point 
+    pub geometric_normal: Point, // the flat surface's, when normal is smoothed. faces the same side.
     pub distance: f32, // distance from camera to collision.
     pub front_face: bool, // did the ray collide the inside or outside (front) of the surface?
     pub uv: Point,
+    pub footprint: f32, // width of the ray's pixel at the hit in uv units, 0 if unknown.
     pub material: Material // the type of material collided
 }
 
 impl RayCollision {
     pub fn new(ray: Ray, normal: Point, distance: f32, material: Material) -> Self {
+        let mut collision = RayCollision::new_uv(ray, normal, distance, Point::origin(), material);
+
+        // spherical uv from the normal, good for spheres. 
+        let u = normal.c.atan2(-collision.normal.a) / (2.0 * PI);
+        let v = (-collision.normal.b).acos() / PI;
+        collision.uv = Point::new(u, v, 0.0);
+
+        collision
+    }
+
+    // for shapes that know their own surface parameterisation.
+    pub fn new_uv(ray: Ray, normal: Point, distance: f32, uv: Point, material: Material) -> Self {
 
         // positive dot product -> vectors are in same direction.
         // negative dot product -> vectors are different directions.
@@ -38,19 +61,33 @@ impl RayCollision {
         let outward_normal: Point = if is_outward { normal } 
                                     else { normal.scalar_mul(-1.0) }; 
 
-        // let hit_point = ray.at(distance);
-        let u = normal.c.atan2(-1.0 * outward_normal.a) / (2.0 * PI);
-        let v = (-1.0 * outward_normal.b).acos() / PI;
-
         RayCollision { 
             hit_point: ray.at(distance), 
+            local_point: ray.at(distance),
             normal: outward_normal, 
+            geometric_normal: outward_normal,
             distance,
             front_face: is_outward,
+            footprint: 0.0,
             material,
-            uv: Point::new(u, v, 0.0)
+            uv
         }
     }
+
+    // for shapes with smoothed normals, the normal of the flat face hit.
+    pub fn with_geometric_normal(mut self, normal: Point) -> Self {
+        let facing = Vector3::dot(&normal, &self.normal) >= 0.0;
+        self.geometric_normal = if facing { normal } else { normal.scalar_mul(-1.0) };
+        self
+    }
+
+    // sets the footprint from how far ray travelled and how many uv units
+    // the shape fits in one unit of its surface. grazing angles stretch the
+    // real footprint, that's left out to keep textures sharp.
+    pub fn with_footprint(mut self, ray: Ray, uv_per_unit: f32) -> Self {
+        self.footprint = ray.spread * self.distance * ray.direction.len() * uv_per_unit;
+        self
+    }
 }
 
 /*
@@ -60,39 +97,234 @@ impl RayCollision {
 #[derive(Clone)]
 pub struct World {
     pub objects: Vec<Shape>,
+    pub use_bvh: bool, // turn off to test every object for every ray (debugging).
+    pub environment: Environment, // what rays that hit nothing see.
+    bvh: Option<Bvh>,
+    unbounded: Vec<usize>, // objects without a bounding box, always tested.
+    lights: Vec<usize>, // glowing objects that can be sampled directly, in order.
+    emitters: Vec<Light>, // how to sample each of lights.
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new()
+    }
 }
 
 impl World {
     pub fn new() -> Self {
-        World { objects: vec![] }
+        World { objects: vec![], use_bvh: true, environment: Environment::default(), bvh: None, unbounded: vec![], lights: vec![], emitters: vec![] }
     }
 
-    pub fn insert<'a>(&mut self, object: Shape) {
+    // inserting invalidates the bvh, call build_bvh again before rendering.
+    pub fn insert(&mut self, object: Shape) {
+        if let Some(light) = Light::from_shape(&object) {
+            self.lights.push(self.objects.len());
+            self.emitters.push(light);
+        }
+
         self.objects.push(object);
+        self.bvh = None;
     }
 
     pub fn clear(mut self) -> Self {
         self.objects = vec![];
+        self.bvh = None;
+        self.unbounded = vec![];
+        self.lights = vec![];
+        self.emitters = vec![];
         self
     }
-}
 
-impl Hittable for World {
-    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+    pub fn lights(&self) -> &[usize] {
+        &self.lights
+    }
+
+    // a direction towards one of the lights, picked evenly. the pdf includes
+    // the odds of picking that light.
+    pub fn sample_light(&self, origin: Point, time: f32, sampler: &mut SamplerKind) -> Option<(usize, LightSample)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let pick = ((sampler.next_1d() * self.lights.len() as f32) as usize).min(self.lights.len() - 1);
+        let index = self.lights[pick];
+
+        let mut sample = self.emitters[pick].sample(origin, time, sampler.next_2d())?;
+        sample.pdf /= self.lights.len() as f32;
+
+        Some((index, sample))
+    }
+
+    // the pdf sample_light would have had for finding collision on object
+    // index from origin. 0 for objects that aren't sampled lights.
+    pub fn light_pdf(&self, index: usize, origin: Point, time: f32, collision: &RayCollision) -> f32 {
+        // lights are pushed in insertion order, so they're sorted.
+        let Ok(pick) = self.lights.binary_search(&index) else {
+            return 0.0;
+        };
+
+        self.emitters[pick].pdf(origin, time, collision) / self.lights.len() as f32
+    }
+
+    // like hit, but also says which object was hit.
+    pub fn hit_object(&self, ray: Ray) -> Option<(usize, RayCollision)> {
+        match self.active_bvh() {
+            Some(bvh) => self.hit_bvh(bvh, ray),
+            None => self.hit_linear(ray),
+        }
+    }
+
+    pub fn build_bvh(&mut self, method: SplitMethod) {
+        let mut boxes: Vec<(usize, Aabb)> = vec![];
+        self.unbounded = vec![];
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            match obj.bounding_box() {
+                Some(b) => boxes.push((i, b)),
+                None => self.unbounded.push(i),
+            }
+        }
+
+        self.bvh = Some(Bvh::build(&boxes, method));
+    }
+
+    // the bvh is only used if it was built for the current set of objects.
+    fn active_bvh(&self) -> Option<&Bvh> {
+        if !self.use_bvh {
+            return None;
+        }
+
+        self.bvh
+            .as_ref()
+            .filter(|bvh| bvh.len() + self.unbounded.len() == self.objects.len())
+    }
+
+    fn hit_linear(&self, ray: Ray) -> Option<(usize, RayCollision)> {
         // calculate each collision through ray. 
         // my god this is a nightmare O.O
         self.objects 
             .iter()
-            .map(|obj| obj.hit(ray))
-            .filter(|obj| {
-                match obj {
-                    Some(_) => return true,
-                    None => return false
-                }
-            })
-            .map(|obj| obj.unwrap())
-            .min_by(|x, y| {
+            .enumerate()
+            .filter_map(|(i, obj)| obj.hit(ray).map(|c| (i, c)))
+            .min_by(|(_, x), (_, y)| {
                 x.distance.to_owned().total_cmp(&y.distance)
             })
     }
-}
\ No newline at end of file
+
+    fn hit_bvh(&self, bvh: &Bvh, ray: Ray) -> Option<(usize, RayCollision)> {
+        let mut closest = bvh.traverse(ray, |i| self.objects[i].hit(ray));
+
+        // unbounded objects can't live in the tree. same tie-break as the
+        // traversal so the result matches the linear scan.
+        for &i in &self.unbounded {
+            if let Some(c) = self.objects[i].hit(ray) {
+                let better = match &closest {
+                    None => true,
+                    Some((best_i, best)) => c.distance < best.distance
+                        || (c.distance == best.distance && i < *best_i),
+                };
+
+                if better {
+                    closest = Some((i, c));
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl Hittable for World {
+    fn hit(&self, ray: Ray) -> Option<RayCollision> {
+        self.hit_object(ray).map(|(_, c)| c)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects
+            .iter()
+            .try_fold(Aabb::empty(), |acc, obj| obj.bounding_box().map(|b| acc.union(b)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::rng::Pcg32;
+    use crate::shapes::plane::Plane;
+    use crate::shapes::sphere::Sphere;
+    use crate::util::texture::Texture;
+
+    fn matte(shade: f32) -> Material {
+        Material::Lambertian(Texture::Solid(Color::new(shade, shade, shade)))
+    }
+
+    fn random_point(rng: &mut Pcg32, size: f32) -> Point {
+        Point::new(rng.next_f32() - 0.5, rng.next_f32() - 0.5, rng.next_f32() - 0.5).scalar_mul(size)
+    }
+
+    // the same object and distance with and without the bvh.
+    fn assert_same_hits(world: &World, rays: &[Ray]) {
+        let mut linear = world.clone();
+        linear.use_bvh = false;
+
+        for ray in rays {
+            let a = world.hit_object(*ray);
+            let b = linear.hit_object(*ray);
+
+            match (a, b) {
+                (None, None) => {},
+                (Some((i, x)), Some((j, y))) => {
+                    assert_eq!(i, j, "different objects for {:?}", ray);
+                    assert_eq!(x.distance, y.distance);
+                    assert_eq!(format!("{:?}", x.material), format!("{:?}", y.material));
+                },
+                (a, b) => panic!("bvh hit {:?}, linear hit {:?}", a.map(|h| h.0), b.map(|h| h.0)),
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_matches_linear_scan() {
+        let mut rng = Pcg32::new(7, 0);
+
+        for method in [SplitMethod::Midpoint, SplitMethod::Sah] {
+            let mut world = World::new();
+            for i in 0..200 {
+                let center = random_point(&mut rng, 20.0);
+                let radius = 0.2 + rng.next_f32();
+                world.insert(Shape::sphere(Sphere::new_pos_t(center, matte(i as f32 / 200.0), radius)));
+            }
+            // unbounded, so it's tested outside the tree.
+            world.insert(Shape::plane(Plane::new(Point::new(0.0, -8.0, 0.0), Point::new(0.0, 1.0, 0.0), matte(1.0))));
+            world.build_bvh(method);
+
+            let rays: Vec<Ray> = (0..2000)
+                .map(|_| Ray::new(random_point(&mut rng, 30.0), random_point(&mut rng, 2.0)))
+                .collect();
+
+            assert_same_hits(&world, &rays);
+        }
+    }
+
+    #[test]
+    fn equal_distances_go_to_the_first_object() {
+        let mut world = World::new();
+
+        // enough copies to fill more than one leaf.
+        for i in 0..10 {
+            world.insert(Shape::sphere(Sphere::new_pos_t(Point::origin(), matte(i as f32 / 10.0), 1.0)));
+        }
+        // touching the front of the spheres, a tie between the tree and the
+        // unbounded list.
+        world.insert(Shape::plane(Plane::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, 1.0), matte(1.0))));
+        world.build_bvh(SplitMethod::Sah);
+
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0));
+        let (index, hit) = world.hit_object(ray).unwrap();
+
+        assert_eq!(index, 0);
+        assert_eq!(hit.distance, 4.0);
+        assert_same_hits(&world, &[ray]);
+    }
+}
@@ -1,85 +1,314 @@
-// use std::thread;
-// use std::sync::mpsc;
-
 use std::io::{self, Write};
-// use std::thread;
-// use std::sync::*;
-// use std::thread::JoinHandle;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::math::vector::Point;
+use crate::math::vector::*;
 use crate::util::camera::Camera;
+use crate::util::framebuffer::{Accumulator, Framebuffer};
 use crate::util::hittable::*;
+use crate::util::integrator::IntegratorKind;
+use crate::util::sampler::SamplerKind;
+use crate::util::output::{self, OutputFormat};
+
+// side length of the square tiles handed out to render threads.
+const TILE_SIZE: i32 = 32;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Image {
     pub width: i32,
     pub height: i32,
     pub samples_per_pixel: i32,
-    pub fov: f32
+    pub fov: f32,
+    pub max_depth: i32, // how many times a ray may bounce.
+    pub threads: usize, // render worker count, defaults to every core.
+    pub seed: Option<u64>, // fixed seed for repeatable renders.
+    pub integrator: IntegratorKind,
+    pub sampler: SamplerKind,
+    pub progressive: Option<Progressive>, // render in passes, see draw.
+}
+
+// rendering in passes: samples pile up a few per pixel at a time, the image
+// so far is written out every so often, and the render can be cut short
+// by a time limit or ctrl-c and still leave a finished image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Progressive {
+    pub pass_samples: i32, // samples per pixel added each pass.
+    pub write_interval: Duration, // time between writes of the image so far.
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Progressive { pass_samples: 4, write_interval: Duration::from_secs(10), time_limit: None }
+    }
 }
 
 pub struct DrawHeader<'a> {
-    pub output_file: &'a str, 
+    pub output_file: &'a str,
+    pub format: OutputFormat,
     pub camera: &'a Camera,
     pub world: &'a World,
+    pub stop: &'a AtomicBool, // set to end a progressive render early.
 }
 
 #[derive(Clone)]
-pub struct RenderObject {
+pub struct RenderObject<'a> {
     pub coordinate: Point,
     pub image: Image,
-    pub camera: Camera,
-    pub world: World,
+    pub camera: &'a Camera,
+    pub world: &'a World,
+    pub sampler: SamplerKind, // prepared for this render.
+}
+
+// a rectangle of pixels, x0..x1 and y0..y1 with y = 0 at the top row.
+#[derive(Copy, Clone, Debug)]
+struct Tile {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
 }
 
 impl Image {
     pub fn new(width: i32, height: i32, fov: f32, samples: i32) -> Self {
         println!("Created a new {}x{} image!", width, height);
-        Image { width, height, fov, samples_per_pixel: samples }
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Image { width, height, fov, samples_per_pixel: samples, max_depth: 20, threads, seed: None, integrator: IntegratorKind::default(), sampler: SamplerKind::default(), progressive: None }
     }
 
-    pub fn draw(&self, header: &DrawHeader) -> std::io::Result<()> {
-        // let mut render_contents: String = format!("P3\n{} {}\n255\n", self.width, self.height);
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = vec![];
 
-        // render each pixel of this image, pixel-by-pixel. 
-        print!("\nCreating framebuffer at {} samples/pixel...\n", self.samples_per_pixel);
+        for y0 in (0..self.height).step_by(TILE_SIZE as usize) {
+            for x0 in (0..self.width).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(self.width),
+                    y1: (y0 + TILE_SIZE).min(self.height),
+                });
+            }
+        }
 
-        // let mut row_contents: Vec<JoinHandle<String>> = vec![];
+        tiles
+    }
+
+    // sums of samples of every pixel in tile, row by row.
+    fn render_tile(&self, tile: Tile, header: &DrawHeader, seed: u64, samples: Range<u32>) -> Vec<Color> {
+        // every pixel sample is seeded from seed, its pixel and its index,
+        // so the result doesn't depend on which thread renders the tile.
         let mut render_object = RenderObject {
             coordinate: Point::origin(),
             image: self.to_owned(),
-            camera: header.camera.to_owned(),
-            world: header.world.to_owned()
+            camera: header.camera,
+            world: header.world,
+            sampler: self.sampler.prepare(self.samples_per_pixel as u32, seed),
         };
 
-        // goofy mess that attempts to save memory 
-        let image = 
-            format!("P3\n{} {}\n255\n", self.width, self.height) // output head 
-            + // plus the image contents
-            (0..self.height).rev().map(|y| {
-                let row = (0..self.width).map(|x| {
-                    render_object.coordinate = Point::new(x as f32, y as f32, 0.0).to_owned();
+        let mut pixels = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+        for row in tile.y0..tile.y1 {
+            // camera v runs bottom -> top, rows run top -> bottom.
+            let y = self.height - 1 - row;
+
+            for x in tile.x0..tile.x1 {
+                render_object.coordinate = Point::new(x as f32, y as f32, 0.0);
+                pixels.push(header.camera.sample_pixel(&render_object, samples.clone()));
+            }
+        }
+
+        pixels
+    }
+
+    // without a seed every render is different. printing the one picked
+    // lets a render be repeated exactly with --seed.
+    fn pick_seed(&self) -> u64 {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("Seed: {}", seed);
+        seed
+    }
+
+    fn workers(&self) -> usize {
+        self.threads.clamp(1, self.tiles().len().max(1))
+    }
+
+    // adds samples of every pixel to accumulator, on a pool of self.threads
+    // workers. tiles are pulled off a shared counter, so fast tiles don't
+    // leave threads idle, and are added by position so the output doesn't
+    // depend on which thread rendered what. workers stop taking tiles once
+    // should_stop says so. returns whether every tile was rendered.
+    fn render_pass(
+        &self,
+        header: &DrawHeader,
+        seed: u64,
+        samples: Range<u32>,
+        accumulator: &Mutex<Accumulator>,
+        should_stop: &(dyn Fn() -> bool + Sync),
+        label: &str,
+    ) -> bool {
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let tiles_done = AtomicUsize::new(0);
+        let count = samples.len() as u32;
+
+        thread::scope(|scope| {
+            for _ in 0..self.workers() {
+                scope.spawn(|| loop {
+                    if should_stop() {
+                        break;
+                    }
+
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let Some(&tile) = tiles.get(index) else { break };
+
+                    let sums = self.render_tile(tile, header, seed, samples.clone());
+
+                    let mut accumulator = accumulator.lock().unwrap();
+                    let tile_width = (tile.x1 - tile.x0) as usize;
+                    for (i, row) in (tile.y0..tile.y1).enumerate() {
+                        let start = (row * self.width + tile.x0) as usize;
+                        for (j, &sum) in sums[i * tile_width..(i + 1) * tile_width].iter().enumerate() {
+                            accumulator.add(start + j, sum, count);
+                        }
+                    }
+                    drop(accumulator);
 
-                    let color_str = render_object.camera
-                        .sample_pixel(&render_object)
-                        .scalar_mul(255.0)
-                        .to_pixel();
+                    let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                    print!("\r{}: {}%", label, 100 * done / tiles.len());
+                    let _ = io::stdout().flush();
+                });
+            }
+        });
 
-                    // render_contents.push_str(&color_str);
-                    color_str
-                }).collect::<String>();
+        println!();
+        tiles_done.into_inner() == tiles.len()
+    }
+
+    // renders every sample of every pixel in one go.
+    pub fn render(&self, header: &DrawHeader) -> Framebuffer {
+        print!("\nCreating framebuffer at {} samples/pixel on {} threads...\n", self.samples_per_pixel, self.workers());
+        let seed = self.pick_seed();
+
+        let accumulator = Mutex::new(Accumulator::new(self.width as u32, self.height as u32));
+        self.render_pass(header, seed, 0..self.samples_per_pixel as u32, &accumulator, &|| false, "Framebuffer");
+
+        accumulator.into_inner().unwrap().to_framebuffer()
+    }
+
+    // renders samples_per_pixel in passes of progressive.pass_samples,
+    // writing the image so far every write_interval. stops early once
+    // the time limit is up or header.stop is set, keeping every sample
+    // finished by then.
+    pub fn render_progressive(&self, header: &DrawHeader, progressive: Progressive) -> io::Result<Framebuffer> {
+        print!("\nRendering {} samples/pixel in passes of {} on {} threads...\n", self.samples_per_pixel, progressive.pass_samples, self.workers());
+        let seed = self.pick_seed();
+
+        let start = Instant::now();
+        let deadline = progressive.time_limit.map(|limit| start + limit);
+        let should_stop = || header.stop.load(Ordering::Relaxed) || deadline.is_some_and(|d| Instant::now() >= d);
+
+        let accumulator = Mutex::new(Accumulator::new(self.width as u32, self.height as u32));
+        let total = self.samples_per_pixel.max(1) as u32;
+        let pass_samples = progressive.pass_samples.max(1) as u32;
+
+        let mut done = 0;
+        let mut last_write = start;
+        for pass in 1.. {
+            if done >= total || should_stop() {
+                break;
+            }
+
+            let samples = done..(done + pass_samples).min(total);
+            let label = format!("Pass {} ({}/{} samples/pixel)", pass, samples.end, total);
+
+            if !self.render_pass(header, seed, samples.clone(), &accumulator, &should_stop, &label) {
+                break;
+            }
+            done = samples.end;
+
+            if done < total && last_write.elapsed() >= progressive.write_interval {
+                let framebuffer = accumulator.lock().unwrap().to_framebuffer();
+                output::write_replacing(&framebuffer, header.output_file, header.format)?;
+                last_write = Instant::now();
+            }
+        }
+
+        if done < total {
+            println!("Stopped after {:.1} seconds, {} of {} samples/pixel done.", start.elapsed().as_secs_f32(), done, total);
+        }
+
+        Ok(accumulator.into_inner().unwrap().to_framebuffer())
+    }
+
+    pub fn draw(&self, header: &DrawHeader) -> std::io::Result<()> {
+        match self.progressive {
+            None => output::write(&self.render(header), header.output_file, header.format),
+            Some(progressive) => {
+                let framebuffer = self.render_progressive(header, progressive)?;
+                output::write_replacing(&framebuffer, header.output_file, header.format)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::scene::Scene;
+    use crate::util::texture::TextureCache;
 
-                let percentage_complete: i32 = (100.0 * (1.0 - (y as f32 / self.height as f32))) as i32;
-                print!("\rFramebuffer: {}%", percentage_complete);
-                let _ = io::stdout().flush();
+    // big enough for several tiles, with bounces off glass and a light.
+    const SCENE: &str = "
+        image { width = 70 height = 40 samples = 3 max_depth = 4 seed = 11 }
+        camera { up = (0, 1, 0) at = (0, 0, 3) to = (0, 0, 0) }
+        background { color = (0.2, 0.3, 0.4) }
+        material glass = dielectric { ior = 1.5 }
+        material matte = lambertian { color = (0.7, 0.6, 0.5) }
+        material lamp = light { color = (4, 4, 4) }
+        sphere { center = (-0.5, 0, 0) radius = 0.5 material = glass }
+        sphere { center = (0.6, 0, -0.5) radius = 0.5 material = matte }
+        sphere { center = (0, 2, 0) radius = 0.5 material = lamp }
+        sphere { center = (0, -100.5, 0) radius = 100 material = matte }
+    ";
 
-                row
-            }).collect::<String>().as_str();
+    fn render(scene: &Scene, threads: usize, sampler: SamplerKind, seed: u64) -> Vec<f32> {
+        let stop = AtomicBool::new(false);
+        let header = DrawHeader {
+            output_file: "unused.png",
+            format: OutputFormat::Png,
+            camera: &scene.camera,
+            world: &scene.world,
+            stop: &stop,
+        };
+
+        let image = Image { threads, sampler, seed: Some(seed), ..scene.image };
+        image.render(&header).to_rgb32f()
+    }
+
+    #[test]
+    fn same_seed_same_image_on_any_thread_count() {
+        let scene = Scene::parse(SCENE, Path::new(""), TextureCache::new(false)).unwrap();
+
+        for name in ["independent", "stratified", "halton", "sobol"] {
+            let sampler = SamplerKind::from_name(name).unwrap();
+            let one = render(&scene, 1, sampler, 11);
+
+            assert!(one.iter().any(|&v| v > 0.0));
+            assert_eq!(one, render(&scene, 3, sampler, 11), "{} on 3 threads", name);
+            assert_eq!(one, render(&scene, 8, sampler, 11), "{} on 8 threads", name);
+        }
+    }
 
-        print!("\n");
+    #[test]
+    fn different_seeds_differ() {
+        let scene = Scene::parse(SCENE, Path::new(""), TextureCache::new(false)).unwrap();
+        let sampler = SamplerKind::from_name("independent").unwrap();
 
-        use std::fs;
-        fs::write(header.output_file, image)?;
-        Ok(())
+        assert_ne!(render(&scene, 2, sampler, 1), render(&scene, 2, sampler, 2));
     }
 }
@@ -0,0 +1,303 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::math::vector::*;
+use crate::math::ray::Ray;
+use crate::math::sampling;
+use crate::util::hittable::*;
+use crate::util::light::power_heuristic;
+use crate::util::material::*;
+use crate::util::sampler::{Sampler, SamplerKind};
+use crate::util::texture::Texture;
+
+// Turns a camera ray into the light coming back along it.
+pub trait Integrator {
+    fn li(&self, ray: Ray, world: &World, depth: i32, sampler: &mut SamplerKind) -> Color;
+}
+
+// Wrapper integrator type, so the render settings can stay Copy and have no
+// need for dyn Integrator. picked with --integrator or image { integrator }.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IntegratorKind {
+    Path(PathTracer),
+    Direct(DirectLighting),
+    AmbientOcclusion(AmbientOcclusion),
+    Debug(DebugView),
+}
+
+impl Default for IntegratorKind {
+    fn default() -> Self {
+        IntegratorKind::Path(PathTracer::default())
+    }
+}
+
+impl IntegratorKind {
+    pub const NAMES: &'static str = "path, direct, ao, normals, uv, depth, material";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "path" => Some(IntegratorKind::Path(PathTracer::default())),
+            "direct" => Some(IntegratorKind::Direct(DirectLighting)),
+            "ao" => Some(IntegratorKind::AmbientOcclusion(AmbientOcclusion { distance: f32::INFINITY })),
+            "normals" => Some(IntegratorKind::Debug(DebugView::Normals)),
+            "uv" => Some(IntegratorKind::Debug(DebugView::Uv)),
+            "depth" => Some(IntegratorKind::Debug(DebugView::Depth)),
+            "material" => Some(IntegratorKind::Debug(DebugView::MaterialId)),
+            _ => None,
+        }
+    }
+}
+
+impl Integrator for IntegratorKind {
+    fn li(&self, ray: Ray, world: &World, depth: i32, sampler: &mut SamplerKind) -> Color {
+        match self {
+            IntegratorKind::Path(i) => i.li(ray, world, depth, sampler),
+            IntegratorKind::Direct(i) => i.li(ray, world, depth, sampler),
+            IntegratorKind::AmbientOcclusion(i) => i.li(ray, world, depth, sampler),
+            IntegratorKind::Debug(i) => i.li(ray, world, depth, sampler),
+        }
+    }
+}
+
+/*
+ *  Full global illumination.
+ */
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathTracer {
+    pub roulette_depth: i32, // bounces before paths may be cut short at random.
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer { roulette_depth: 5 }
+    }
+}
+
+impl Integrator for PathTracer {
+    // one bounce per loop, no recursion. throughput is how much of the light
+    // found at the current bounce makes it back to the camera.
+    fn li(&self, ray: Ray, world: &World, depth: i32, sampler: &mut SamplerKind) -> Color {
+        let mut radiance = Color::origin();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = ray;
+
+        // pdf of the bounce that sent ray, None for camera rays and
+        // mirror-like bounces.
+        let mut bsdf_pdf: Option<f32> = None;
+
+        for bounce in 0..depth {
+            let Some((index, c)) = world.hit_object(ray) else {
+                radiance = radiance + throughput * world.environment.color(ray.direction);
+                break;
+            };
+
+            let mut emitted = Material::emitted(&c);
+
+            // light sampling at the last bounce could have found this
+            // light as well, only this path's share is kept.
+            if let Some(pdf) = bsdf_pdf {
+                let light_pdf = world.light_pdf(index, ray.origin, ray.time, &c);
+                emitted = emitted.scalar_mul(power_heuristic(pdf, light_pdf));
+            }
+            radiance = radiance + throughput * emitted;
+
+            // receive material dependent scatter ray
+            let scatter = Material::scatter(ray, &c, sampler);
+
+            if !scatter.normal_matches {
+                break;
+            }
+
+            // mirror-like bounces can only go one way, a light sample
+            // would never line up with it.
+            if scatter.pdf.is_some() {
+                radiance = radiance + throughput * direct_light(world, ray, &c, true, sampler);
+            }
+
+            throughput = throughput * scatter.attenuation;
+
+            // russian roulette: dim paths are stopped with some chance, and
+            // the survivors made brighter to make up for it, so the average
+            // stays the same.
+            if bounce + 1 >= self.roulette_depth {
+                let survive = throughput.a.max(throughput.b).max(throughput.c).min(0.95);
+
+                if sampler.next_1d() >= survive {
+                    break;
+                }
+                throughput = throughput.scalar_div(survive);
+            }
+
+            bsdf_pdf = scatter.pdf;
+            ray = scatter.ray;
+        }
+
+        radiance
+    }
+}
+
+/*
+ *  Light straight from the lights, no diffuse bounces. mirrors and glass
+ *  are still followed so they don't turn black.
+ */
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectLighting;
+
+impl Integrator for DirectLighting {
+    fn li(&self, ray: Ray, world: &World, depth: i32, sampler: &mut SamplerKind) -> Color {
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = ray;
+
+        for _ in 0..depth {
+            let Some(c) = world.hit(ray) else {
+                return throughput * world.environment.color(ray.direction);
+            };
+
+            let emitted = Material::emitted(&c);
+            let scatter = Material::scatter(ray, &c, sampler);
+
+            if !scatter.normal_matches {
+                return throughput * emitted;
+            }
+
+            if scatter.pdf.is_some() {
+                return throughput * (emitted + direct_light(world, ray, &c, false, sampler));
+            }
+
+            throughput = throughput * scatter.attenuation;
+            ray = scatter.ray;
+        }
+
+        Color::origin()
+    }
+}
+
+/*
+ *  How open the sky is above each point: white where nothing is within
+ *  distance, darker in corners and creases. ignores lights and materials.
+ */
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AmbientOcclusion {
+    pub distance: f32,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn li(&self, ray: Ray, world: &World, _depth: i32, sampler: &mut SamplerKind) -> Color {
+        let Some(c) = world.hit(ray) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+
+        // cosine weighted, so the average is the usual ao integral.
+        let direction = c.normal.to_frame(sampling::cosine_hemisphere(sampler.next_2d()));
+
+        let probe = Ray::new_at(c.hit_point, direction, ray.time);
+        match world.hit(probe) {
+            Some(blocker) if blocker.distance < self.distance => Color::origin(),
+            _ => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/*
+ *  False colour views of what the camera hits. misses are black.
+ */
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugView {
+    Normals, // facing the camera, mapped from -1..1 to 0..1.
+    Uv, // u in red, v in green, repeating every unit.
+    Depth, // white up close, fading to black with distance.
+    MaterialId, // a made up colour per distinct material.
+}
+
+impl Integrator for DebugView {
+    fn li(&self, ray: Ray, world: &World, _depth: i32, _sampler: &mut SamplerKind) -> Color {
+        let Some(c) = world.hit(ray) else {
+            return Color::origin();
+        };
+
+        match self {
+            DebugView::Normals => (c.normal + Color::new(1.0, 1.0, 1.0)).scalar_mul(0.5),
+            DebugView::Uv => Color::new(c.uv.a.rem_euclid(1.0), c.uv.b.rem_euclid(1.0), 0.0),
+            DebugView::Depth => {
+                let distance = c.distance * ray.direction.len();
+                let shade = 1.0 / (1.0 + distance);
+                Color::new(shade, shade, shade)
+            },
+            DebugView::MaterialId => material_color(&c.material),
+        }
+    }
+}
+
+// same material, same colour. image and procedural textures are told apart
+// by which one they share rather than by their contents.
+fn material_color(material: &Material) -> Color {
+    let mut hasher = DefaultHasher::new();
+
+    let hash_texture = |texture: &Texture, hasher: &mut DefaultHasher| match texture {
+        Texture::Solid(c) => [c.a, c.b, c.c].map(f32::to_bits).hash(hasher),
+        Texture::Img(img) => (Arc::as_ptr(img) as usize).hash(hasher),
+        Texture::Checker(checker) => (Arc::as_ptr(checker) as usize).hash(hasher),
+        Texture::Noise(noise) => (Arc::as_ptr(noise) as usize).hash(hasher),
+    };
+
+    match material {
+        Material::Metal { albedo, fuzz } => {
+            (0, fuzz.to_bits()).hash(&mut hasher);
+            hash_texture(albedo, &mut hasher);
+        },
+        Material::Lambertian(texture) => {
+            1.hash(&mut hasher);
+            hash_texture(texture, &mut hasher);
+        },
+        Material::Dielectric(ior) => (2, ior.to_bits()).hash(&mut hasher),
+        Material::DiffuseLight { texture, intensity } => {
+            (3, intensity.to_bits()).hash(&mut hasher);
+            hash_texture(texture, &mut hasher);
+        },
+        Material::Pbr(pbr) => {
+            (4, pbr.ior.to_bits()).hash(&mut hasher);
+            for texture in [&pbr.base_color, &pbr.roughness, &pbr.metallic] {
+                hash_texture(texture, &mut hasher);
+            }
+        },
+    }
+
+    // three bytes of the hash, kept away from black.
+    let h = hasher.finish();
+    let channel = |shift: u64| 0.2 + 0.8 * ((h >> shift) & 0xFF) as f32 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+// next-event estimation: light reaching the collision (of ray) straight
+// from a randomly picked light, if nothing is in the way. with mis the
+// sample is weighted against the bsdf having found the same light.
+pub fn direct_light(world: &World, ray: Ray, c: &RayCollision, mis: bool, sampler: &mut SamplerKind) -> Color {
+    let time = ray.time;
+    let outgoing = ray.direction.scalar_mul(-1.0);
+
+    let Some((index, sample)) = world.sample_light(c.hit_point, time, sampler) else {
+        return Color::origin();
+    };
+
+    let bsdf = Material::eval(c, outgoing, sample.direction);
+    if bsdf.a + bsdf.b + bsdf.c <= 0.0 {
+        return Color::origin();
+    }
+
+    let shadow_ray = Ray::new_at(c.hit_point, sample.direction, time);
+
+    match world.hit_object(shadow_ray) {
+        // hit in front of the sampled point means another part of the
+        // light is in the way.
+        Some((hit, light_hit)) if hit == index && light_hit.distance >= sample.distance * 0.999 => {
+            let weight = if mis { power_heuristic(sample.pdf, Material::pdf(c, outgoing, sample.direction)) } else { 1.0 };
+            Material::emitted(&light_hit) * bsdf.scalar_mul(weight / sample.pdf)
+        },
+        _ => Color::origin(),
+    }
+}
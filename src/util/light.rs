@@ -0,0 +1,302 @@
+use std::f32::consts::PI;
+
+use crate::math::matrix::Matrix4;
+use crate::math::sampling;
+use crate::math::vector::*;
+use crate::shapes::shape::Shape;
+use crate::shapes::sphere::Sphere;
+use crate::shapes::triangle::face_normal;
+use crate::util::hittable::RayCollision;
+use crate::util::material::Material;
+
+/*
+ *  Picking directions towards lights, for next-event estimation. Glowing
+ *  shapes are turned into lights when they're added to the world: spheres
+ *  are sampled by the cone of directions they cover, everything flat by
+ *  area over its glowing triangles, in world space, through any instances.
+ *  Infinite planes and spheres stretched into ellipsoids can't be sampled,
+ *  and are still found by rays that bounce into them.
+ */
+
+// a direction from origin towards a light, and how likely sample() was to
+// pick it (per unit solid angle).
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    pub direction: Point,
+    pub pdf: f32,
+    // how far away the sampled point is. the light has to be hit there,
+    // not in front, or the sample was for a hidden part of it. 0 when any
+    // hit on the light will do.
+    pub distance: f32,
+}
+
+#[derive(Clone, Debug)]
+pub enum Light {
+    // the sphere as placed by to_world, which can only move and turn it,
+    // and scale it evenly by scale.
+    Sphere { sphere: Sphere, to_world: Matrix4, scale: f32 },
+    // picked by area, cdf holds the running total of the areas.
+    Triangles { triangles: Vec<[Point; 3]>, cdf: Vec<f32>, area: f32 },
+}
+
+fn glows(material: &Material) -> bool {
+    matches!(material, Material::DiffuseLight { .. })
+}
+
+impl Light {
+    // None for shapes that don't glow, or can't be sampled (with a warning).
+    pub fn from_shape(shape: &Shape) -> Option<Light> {
+        let mut triangles = vec![];
+        let sphere = Light::gather(shape, Matrix4::identity(), &mut triangles);
+
+        if let Some(light) = sphere {
+            return Some(light);
+        }
+
+        let mut cdf = Vec::with_capacity(triangles.len());
+        let mut area = 0.0;
+        for [p0, p1, p2] in &triangles {
+            area += triangle_area(*p0, *p1, *p2);
+            cdf.push(area);
+        }
+
+        (area > 0.0).then_some(Light::Triangles { triangles, cdf, area })
+    }
+
+    // collects the glowing triangles of shape into triangles, or returns
+    // the light for a glowing sphere.
+    fn gather(shape: &Shape, to_world: Matrix4, triangles: &mut Vec<[Point; 3]>) -> Option<Light> {
+        let corners = |p: [Point; 3]| p.map(|p| to_world.transform_point(p));
+
+        match shape {
+            Shape::Sphere(s) if glows(&s.material) => match even_scale(&to_world) {
+                Some(scale) => return Some(Light::Sphere { sphere: s.clone(), to_world, scale }),
+                None => eprintln!("Warning: a glowing sphere scaled unevenly can't be sampled as a light, it will be noisy"),
+            },
+            Shape::Quad(q) if glows(&q.material) => {
+                let (a, b, c, d) = (q.origin, q.origin + q.u, q.origin + q.u + q.v, q.origin + q.v);
+                triangles.push(corners([a, b, c]));
+                triangles.push(corners([a, c, d]));
+            },
+            Shape::Triangle(t) if glows(&t.material) => triangles.push(corners(t.vertices)),
+            Shape::Mesh(m) => {
+                for i in 0..m.data.indices.len() {
+                    let material = m.data.material_ids.get(i).copied().unwrap_or(0);
+                    if glows(&m.materials[material]) {
+                        let (p0, p1, p2) = m.data.corners(i);
+                        triangles.push(corners([p0, p1, p2]));
+                    }
+                }
+            },
+            Shape::Plane(p) if glows(&p.material) => {
+                eprintln!("Warning: a glowing plane is infinite and can't be sampled as a light, it will be noisy");
+            },
+            Shape::Instance(i) => return Light::gather(&i.object, to_world * i.transform(), triangles),
+            _ => {},
+        }
+
+        None
+    }
+
+    // u is a 2d sample from the sampler.
+    pub fn sample(&self, origin: Point, time: f32, u: (f32, f32)) -> Option<LightSample> {
+        match self {
+            Light::Sphere { sphere, to_world, scale } => {
+                // uniform over the cone of directions the sphere covers.
+                let to_center = to_world.transform_point(sphere.center_at(time)) + origin.scalar_mul(-1.0);
+                let cos_max = cone_cos_max(to_center, sphere.radius * scale)?;
+
+                let direction = to_center.unit().to_frame(sampling::uniform_cone(u, cos_max));
+
+                Some(LightSample { direction, pdf: cone_pdf(cos_max), distance: 0.0 })
+            },
+            Light::Triangles { triangles, cdf, area } => {
+                // u.0 picks a triangle by area, what's left of it places the
+                // point, so no extra dimension is used.
+                let target = u.0 * area;
+                let pick = cdf.partition_point(|&total| total <= target).min(triangles.len() - 1);
+                let start = if pick == 0 { 0.0 } else { cdf[pick - 1] };
+                let width = cdf[pick] - start;
+                let u0 = if width > 0.0 { ((target - start) / width).clamp(0.0, 1.0) } else { 0.0 };
+
+                // uniform over the area, converted to solid angle.
+                let [p0, p1, p2] = triangles[pick];
+                let (s, t) = sampling::uniform_triangle((u0, u.1));
+                let point = p0 + (p1 + p0.scalar_mul(-1.0)).scalar_mul(s) + (p2 + p0.scalar_mul(-1.0)).scalar_mul(t);
+                let offset = point + origin.scalar_mul(-1.0);
+
+                let distance_squared = Vector3::dot(&offset, &offset);
+                let direction = offset.unit();
+                let cosine = Vector3::dot(&direction, &face_normal(p0, p1, p2)).abs();
+
+                if cosine < 1e-6 {
+                    return None;
+                }
+
+                Some(LightSample { direction, pdf: distance_squared / (cosine * area), distance: distance_squared.sqrt() })
+            },
+        }
+    }
+
+    // the pdf sample() would have given for the direction that found
+    // collision on the light, 0 if it could never pick it.
+    pub fn pdf(&self, origin: Point, time: f32, collision: &RayCollision) -> f32 {
+        match self {
+            Light::Sphere { sphere, to_world, scale } => {
+                let to_center = to_world.transform_point(sphere.center_at(time)) + origin.scalar_mul(-1.0);
+                cone_cos_max(to_center, sphere.radius * scale).map_or(0.0, cone_pdf)
+            },
+            Light::Triangles { area, .. } => {
+                let offset = collision.hit_point + origin.scalar_mul(-1.0);
+                let distance_squared = Vector3::dot(&offset, &offset);
+                let cosine = Vector3::dot(&offset.unit(), &collision.geometric_normal).abs();
+
+                if cosine < 1e-6 {
+                    return 0.0;
+                }
+
+                distance_squared / (cosine * area)
+            },
+        }
+    }
+}
+
+// the scale of a transform that only moves, turns and scales the same on
+// every axis, None if it stretches.
+fn even_scale(m: &Matrix4) -> Option<f32> {
+    let axes = [0, 1, 2].map(|j| Point::new(m.m[0][j], m.m[1][j], m.m[2][j]));
+    let scale = axes[0].len();
+    let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * scale.max(1.0);
+
+    let even = axes.iter().all(|a| close(a.len(), scale))
+        && close(Vector3::dot(&axes[0], &axes[1]), 0.0)
+        && close(Vector3::dot(&axes[1], &axes[2]), 0.0)
+        && close(Vector3::dot(&axes[0], &axes[2]), 0.0);
+
+    even.then_some(scale)
+}
+
+fn triangle_area(p0: Point, p1: Point, p2: Point) -> f32 {
+    (p1 + p0.scalar_mul(-1.0)).cross(p2 + p0.scalar_mul(-1.0)).len() / 2.0
+}
+
+// cosine of the cone's half angle, None from inside the sphere.
+fn cone_cos_max(to_center: Point, radius: f32) -> Option<f32> {
+    let distance_squared = Vector3::dot(&to_center, &to_center);
+    let radius_squared = radius * radius;
+
+    if distance_squared <= radius_squared {
+        return None;
+    }
+
+    Some((1.0 - radius_squared / distance_squared).sqrt())
+}
+
+fn cone_pdf(cos_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+}
+
+// how much of a sample to keep when two strategies could have made it.
+// weights for the same path always add up to 1 (veach's power heuristic).
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+
+    if a + b == 0.0 { 0.0 } else { a / (a + b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::math::ray::Ray;
+    use crate::math::rng::Pcg32;
+    use crate::shapes::instance::Instance;
+    use crate::shapes::mesh::{Mesh, MeshData};
+    use crate::shapes::quad::Quad;
+    use crate::util::hittable::Hittable;
+    use crate::util::texture::Texture;
+
+    fn lamp() -> Material {
+        Material::DiffuseLight { texture: Texture::Solid(Color::new(1.0, 1.0, 1.0)), intensity: 1.0 }
+    }
+
+    fn matte() -> Material {
+        Material::Lambertian(Texture::Solid(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn unit_quad(material: Material) -> Arc<Shape> {
+        Arc::new(Shape::quad(Quad::new(Point::origin(), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0), material)))
+    }
+
+    // every sample lands on the shape, where pdf() agrees with it.
+    fn assert_consistent(shape: &Shape, origin: Point) {
+        let light = Light::from_shape(shape).unwrap();
+        let mut rng = Pcg32::new(5, 0);
+
+        for _ in 0..200 {
+            let Some(sample) = light.sample(origin, 0.0, (rng.next_f32(), rng.next_f32())) else { continue };
+            let hit = shape.hit(Ray::new(origin, sample.direction)).expect("sample missed the light");
+
+            assert!(hit.distance >= sample.distance * 0.999);
+            let pdf = light.pdf(origin, 0.0, &hit);
+            assert!((pdf - sample.pdf).abs() <= 1e-3 * sample.pdf, "{} != {}", pdf, sample.pdf);
+        }
+    }
+
+    #[test]
+    fn only_glowing_shapes_are_lights() {
+        assert!(Light::from_shape(&unit_quad(matte())).is_none());
+        assert!(Light::from_shape(&unit_quad(lamp())).is_some());
+    }
+
+    #[test]
+    fn instanced_quads_are_sampled_in_world_space() {
+        let to_world = Matrix4::translate(Point::new(0.0, 3.0, 0.0)) * Matrix4::scale(Point::new(2.0, 1.0, 4.0));
+        let instance = Shape::instance(Instance::new(unit_quad(lamp()), to_world).unwrap());
+
+        match Light::from_shape(&instance) {
+            Some(Light::Triangles { area, .. }) => assert!((area - 8.0).abs() < 1e-5),
+            other => panic!("{:?}", other),
+        }
+        assert_consistent(&instance, Point::new(0.5, 0.0, 0.5));
+    }
+
+    #[test]
+    fn meshes_sample_their_glowing_triangles() {
+        // a glowing triangle overhead and a plain one beside it.
+        let data = MeshData {
+            positions: vec![
+                Point::new(0.0, 2.0, 0.0), Point::new(1.0, 2.0, 0.0), Point::new(0.0, 2.0, 1.0),
+                Point::new(5.0, 2.0, 0.0), Point::new(6.0, 2.0, 0.0), Point::new(5.0, 2.0, 1.0),
+            ],
+            indices: vec![[0, 1, 2], [3, 4, 5]],
+            material_ids: vec![1, 0],
+            ..MeshData::default()
+        };
+        let mesh = Shape::mesh(Mesh::from_shared(Arc::new(data), vec![matte(), lamp()]));
+
+        match Light::from_shape(&mesh) {
+            Some(Light::Triangles { triangles, area, .. }) => {
+                assert_eq!(triangles.len(), 1);
+                assert!((area - 0.5).abs() < 1e-5);
+            },
+            other => panic!("{:?}", other),
+        }
+        assert_consistent(&mesh, Point::new(0.2, 0.0, 0.2));
+    }
+
+    #[test]
+    fn spheres_keep_cone_sampling_through_even_scales() {
+        let sphere = Arc::new(Shape::sphere(Sphere::new_pos_t(Point::origin(), lamp(), 1.0)));
+        let to_world = Matrix4::translate(Point::new(0.0, 5.0, 0.0)) * Matrix4::rotate(Point::new(0.0, 30.0, 0.0)) * Matrix4::scale(Point::new(2.0, 2.0, 2.0));
+        let instance = Shape::instance(Instance::new(Arc::clone(&sphere), to_world).unwrap());
+
+        assert!(matches!(Light::from_shape(&instance), Some(Light::Sphere { .. })));
+        assert_consistent(&instance, Point::origin());
+
+        // an ellipsoid can't be, and is left to bsdf sampling.
+        let stretched = Shape::instance(Instance::new(sphere, Matrix4::scale(Point::new(1.0, 3.0, 1.0))).unwrap());
+        assert!(Light::from_shape(&stretched).is_none());
+    }
+}
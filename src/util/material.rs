@@ -1,64 +1,176 @@
+use std::f32::consts::PI;
+
+use crate::math::sampling;
 use crate::math::ray::Ray;
 use crate::util::hittable::RayCollision;
 use crate::math::vector::*;
 
+use super::pbr::Pbr;
+use super::sampler::{Sampler, SamplerKind};
 use super::texture::Texture;
 
 #[derive(Clone, Debug)]
 pub enum Material {
-    Metal(Color),
+    Metal { albedo: Texture, fuzz: f32 }, // fuzz 0 is a mirror, 1 is very rough.
     Lambertian(Texture),
+    Dielectric(f32), // index of refraction. glass is ~1.5, water ~1.33
+    DiffuseLight { texture: Texture, intensity: f32 }, // emits light, doesn't scatter.
+    Pbr(Pbr), // metallic/roughness, see util::pbr.
 }
 
 pub struct ScatterResult {
     pub ray: Ray,
     pub attenuation: Color,
     pub normal_matches: bool,
+    pub pdf: Option<f32>, // chance of picking ray's direction. None for mirror-like bounces light sampling can't help.
 }
 
 impl Material {
-    pub fn scatter(r_in: Ray, collision: &RayCollision) -> ScatterResult {
+    pub fn scatter(r_in: Ray, collision: &RayCollision, sampler: &mut SamplerKind) -> ScatterResult {
+        match &collision.material {
+            Material::Metal { albedo, fuzz } => Material::metal_scatter(albedo, *fuzz, r_in, collision, sampler),
+            Material::Lambertian(texture) => Material::lambertian_scatter(texture, r_in, collision, sampler),
+            Material::Dielectric(ior) => Material::dielectric_scatter(*ior, r_in, collision, sampler),
+            Material::Pbr(pbr) => Material::pbr_scatter(pbr, r_in, collision, sampler),
+            Material::DiffuseLight { .. } => ScatterResult {
+                // lights absorb everything that hits them.
+                ray: r_in,
+                attenuation: Color::origin(),
+                normal_matches: false,
+                pdf: None,
+            },
+        }
+    }
+
+    // light given off at the collision, black for everything but lights.
+    // lights shine from both sides of their surface.
+    pub fn emitted(collision: &RayCollision) -> Color {
+        match &collision.material {
+            Material::DiffuseLight { texture, intensity } => {
+                texture.at(collision).scalar_mul(*intensity)
+            },
+            _ => Color::origin(),
+        }
+    }
+
+    // the bsdf times the cosine term, for light arriving from direction and
+    // leaving towards outgoing. black for materials that only bounce in
+    // one direction.
+    pub fn eval(collision: &RayCollision, outgoing: Point, direction: Point) -> Color {
         match &collision.material {
-            Material::Metal(albedo ) => Material::metal_scatter(albedo.to_owned(), r_in, collision),
-            Material::Lambertian(texture) => Material::lambertian_scatter(texture, collision),
+            Material::Lambertian(texture) => {
+                let cosine = Vector3::dot(&direction.unit(), &collision.normal).max(0.0);
+                texture.at(collision).scalar_mul(cosine / PI)
+            },
+            Material::Pbr(pbr) => pbr.eval(collision, outgoing, direction),
+            _ => Color::origin(),
         }
     }
 
-    fn lambertian_scatter(texture: &Texture, collision: &RayCollision) -> ScatterResult {
-        // standard unit length lambertian scatter with attenuated color
-        let mut scatter_dir: Point = collision.normal + Vector3::rand_in_unit_sphere();
-       
-        // in case scatter dir is the oppose of the normal.
-        if scatter_dir.near_zero() {
-            scatter_dir = collision.normal; 
+    // the pdf scatter would have had for picking direction.
+    pub fn pdf(collision: &RayCollision, outgoing: Point, direction: Point) -> f32 {
+        match &collision.material {
+            Material::Lambertian(_) => Vector3::dot(&direction.unit(), &collision.normal).max(0.0) / PI,
+            Material::Pbr(pbr) => pbr.pdf(collision, outgoing, direction),
+            _ => 0.0,
         }
+    }
 
-        let scattered = Ray::new(collision.hit_point, scatter_dir);
+    fn lambertian_scatter(texture: &Texture, r_in: Ray, collision: &RayCollision, sampler: &mut SamplerKind) -> ScatterResult {
+        // cosine weighted lambertian scatter with attenuated color. the
+        // albedo is the bsdf * cosine / pdf, the rest cancels out.
+        let scatter_dir: Point = collision.normal.to_frame(sampling::cosine_hemisphere(sampler.next_2d()));
 
-        let attenuation = texture.get(&collision.uv.a, &collision.uv.b); 
+        let scattered = Ray::new_at(collision.hit_point, scatter_dir, r_in.time);
+
+        let attenuation = texture.at(collision); 
 
         ScatterResult { 
             ray: scattered, 
             attenuation, 
             normal_matches: true, 
+            pdf: Some(Material::pdf(collision, r_in.direction.scalar_mul(-1.0), scatter_dir)),
+        }
+    }
+
+    fn pbr_scatter(pbr: &Pbr, r_in: Ray, collision: &RayCollision, sampler: &mut SamplerKind) -> ScatterResult {
+        let outgoing = r_in.direction.scalar_mul(-1.0);
+        let (u, u2) = (sampler.next_1d(), sampler.next_2d());
+
+        // the bsdf * cosine / pdf of the direction picked, or absorbed if
+        // the microfacet sent it under the surface.
+        let sample = pbr.sample(collision, outgoing, u, u2).and_then(|direction| {
+            let pdf = pbr.pdf(collision, outgoing, direction);
+            (pdf > 0.0).then_some((direction, pdf))
+        });
+
+        match sample {
+            Some((direction, pdf)) => ScatterResult {
+                ray: Ray::new_at(collision.hit_point, direction, r_in.time),
+                attenuation: pbr.eval(collision, outgoing, direction).scalar_mul(1.0 / pdf),
+                normal_matches: true,
+                pdf: Some(pdf),
+            },
+            None => ScatterResult {
+                ray: r_in,
+                attenuation: Color::origin(),
+                normal_matches: false,
+                pdf: None,
+            },
         }
     }
 
-    fn metal_scatter(albedo: Color, r_in: Ray, collision: &RayCollision) -> ScatterResult {
+    fn metal_scatter(albedo: &Texture, fuzz: f32, r_in: Ray, collision: &RayCollision, sampler: &mut SamplerKind) -> ScatterResult {
         let mut reflection: Point = r_in.direction.unit().reflect(collision.normal);
-        let norm_matches_reflection: bool = Vector3::dot(&reflection, &collision.normal) > 0.0;
 
-        if !norm_matches_reflection {
-            // if this is not done, the reflection is upside-down and is a blob.
-            reflection = reflection.scalar_mul(-1.0);
+        // rough metal nudges the mirror direction somewhere within a sphere
+        // of radius fuzz around its tip, blurring the reflection.
+        if fuzz > 0.0 {
+            let offset = sampling::uniform_sphere(sampler.next_2d()).scalar_mul(fuzz.min(1.0));
+            reflection = reflection + offset;
         }
 
-        let scattered: Ray = Ray::new(collision.hit_point, reflection);
-        
+        let scattered: Ray = Ray::new_at(collision.hit_point, reflection, r_in.time);
+
         ScatterResult {
             ray: scattered,
-            attenuation: albedo,
+            attenuation: albedo.at(collision),
+            // nudged below the surface, the metal absorbs it.
+            normal_matches: Vector3::dot(&reflection, &collision.normal) > 0.0,
+            pdf: None,
+        }
+    }
+
+    // schlick's approximation of the fresnel reflectance.
+    fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
+        let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+        let r0 = r0 * r0;
+
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+
+    fn dielectric_scatter(ior: f32, r_in: Ray, collision: &RayCollision, sampler: &mut SamplerKind) -> ScatterResult {
+        // going from air into the material, or from the material back out.
+        let ratio: f32 = if collision.front_face { 1.0 / ior } else { ior };
+
+        let unit_dir: Point = r_in.direction.unit();
+        let cos_theta: f32 = Vector3::dot(&unit_dir.scalar_mul(-1.0), &collision.normal).min(1.0);
+        let sin_theta: f32 = (1.0 - cos_theta * cos_theta).sqrt();
+
+        // no refraction possible past the critical angle (total internal
+        // reflection), otherwise pick reflect/refract by fresnel reflectance.
+        let cannot_refract: bool = ratio * sin_theta > 1.0;
+        let direction: Point = if cannot_refract || Material::reflectance(cos_theta, ratio) > sampler.next_1d() {
+            unit_dir.reflect(collision.normal)
+        } else {
+            unit_dir.refract(collision.normal, ratio)
+        };
+
+        ScatterResult {
+            ray: Ray::new_at(collision.hit_point, direction, r_in.time),
+            attenuation: Color::new(1.0, 1.0, 1.0), // clear glass absorbs nothing
             normal_matches: true,
+            pdf: None,
         }
     }
 }
@@ -0,0 +1,151 @@
+use crate::math::lerp_vec;
+use crate::math::rng::Pcg32;
+use crate::math::sampling;
+use crate::math::vector::*;
+
+/*
+ *  Perlin noise (perlin 1985, as in 'ray tracing: the next week'): random
+ *  gradients on a lattice, blended smoothly between lattice points. The
+ *  lattice is shuffled from a seed rather than entropy, so a scene looks
+ *  the same every render.
+ */
+
+const POINT_COUNT: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct Perlin {
+    gradients: Vec<Point>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg32::new(seed, 0);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| sampling::uniform_sphere((rng.next_f32(), rng.next_f32())))
+            .collect();
+
+        Perlin {
+            gradients,
+            perm_x: Perlin::permutation(&mut rng),
+            perm_y: Perlin::permutation(&mut rng),
+            perm_z: Perlin::permutation(&mut rng),
+        }
+    }
+
+    // 0..POINT_COUNT shuffled (fisher-yates).
+    fn permutation(rng: &mut Pcg32) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+
+        for i in (1..POINT_COUNT).rev() {
+            let j = rng.next_u32() as usize % (i + 1);
+            p.swap(i, j);
+        }
+
+        p
+    }
+
+    // smooth noise in about -1 to 1, 0 at every lattice point.
+    pub fn noise(&self, p: Point) -> f32 {
+        let (fx, fy, fz) = (p.a.floor(), p.b.floor(), p.c.floor());
+        let (u, v, w) = (p.a - fx, p.b - fy, p.c - fz);
+        let (i, j, k) = (fx as i32, fy as i32, fz as i32);
+
+        // hermite smoothing, so the blend has no creases at cell borders.
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (su, sv, sw) = (smooth(u), smooth(v), smooth(w));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+
+                    let (di, dj, dk) = (di as f32, dj as f32, dk as f32);
+                    let weight = Point::new(u - di, v - dj, w - dk);
+
+                    accum += (di * su + (1.0 - di) * (1.0 - su))
+                        * (dj * sv + (1.0 - dj) * (1.0 - sv))
+                        * (dk * sw + (1.0 - dk) * (1.0 - sw))
+                        * Vector3::dot(&self.gradients[index], &weight);
+                }
+            }
+        }
+
+        accum
+    }
+
+    // octaves of noise, each twice as fine and half as strong as the last
+    // (fractal brownian motion). about -1 to 1.
+    pub fn fbm(&self, p: Point, octaves: u32) -> f32 {
+        let (mut accum, mut weight, mut p) = (0.0, 1.0, p);
+
+        for _ in 0..octaves.max(1) {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = p.scalar_mul(2.0);
+        }
+
+        accum
+    }
+
+    // like fbm, but every octave folded to positive, which gives sharp
+    // creases instead of smooth hills. 0 to about 1.
+    pub fn turbulence(&self, p: Point, octaves: u32) -> f32 {
+        let (mut accum, mut weight, mut p) = (0.0, 1.0, p);
+
+        for _ in 0..octaves.max(1) {
+            accum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p = p.scalar_mul(2.0);
+        }
+
+        accum
+    }
+}
+
+// what a noise texture makes of the noise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Fbm, // soft clouds.
+    Turbulence, // creased, like smoke or fire.
+    Marble, // veins, a sine wave along z pushed around by turbulence.
+    Wood, // rings around the y axis, warped by noise.
+}
+
+// a pattern shaded from low (0) to high (1), in 3d from the hit point.
+#[derive(Clone, Debug)]
+pub struct Noise {
+    pub perlin: Perlin,
+    pub pattern: Pattern,
+    pub scale: f32, // how many noise cells fit in a unit, bigger is finer.
+    pub octaves: u32,
+    pub low: Color,
+    pub high: Color,
+}
+
+impl Noise {
+    pub fn value(&self, point: Point) -> Color {
+        let p = point.scalar_mul(self.scale);
+
+        let t = match self.pattern {
+            Pattern::Fbm => 0.5 * (1.0 + self.perlin.fbm(p, self.octaves)),
+            Pattern::Turbulence => self.perlin.turbulence(p, self.octaves),
+            Pattern::Marble => 0.5 * (1.0 + (p.c + 10.0 * self.perlin.turbulence(p, self.octaves)).sin()),
+            Pattern::Wood => {
+                let radius = (p.a * p.a + p.c * p.c).sqrt() + 0.5 * self.perlin.fbm(p, self.octaves);
+                let ring = (4.0 * radius).rem_euclid(1.0);
+
+                // early wood is wide and light, late wood a thin dark band.
+                ring * ring
+            },
+        };
+
+        lerp_vec(self.low, self.high, t.clamp(0.0, 1.0))
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::math::vector::*;
+use crate::shapes::mesh::*;
+use crate::shapes::shape::Shape;
+use crate::util::material::Material;
+use crate::util::texture::{ImageOptions, Texture, TextureCache, TextureError, Wrap};
+
+/*
+ *  Wavefront .obj/.mtl loading
+ */
+
+// Everything an .mtl "newmtl" block can say that we care about.
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Color, // Kd
+    pub specular: Color, // Ks
+    pub emissive: Color, // Ke
+    pub diffuse_map: Option<PathBuf>, // map_Kd, relative to the .obj
+    pub diffuse_options: ImageOptions, // map_Kd's -s, -o and -clamp
+    pub shininess: Option<f32>, // Ns, 0 - 1000
+    pub ior: f32, // Ni
+    pub dissolve: f32, // d, 1 is opaque. (Tr is 1 - d)
+    pub illum: u32,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_owned(),
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::origin(),
+            emissive: Color::origin(),
+            diffuse_map: None,
+            diffuse_options: ImageOptions::default(),
+            shininess: None,
+            ior: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    // mtl describes phong-style materials, this picks the closest one we have:
+    //   - glowing (Ke above 0) -> DiffuseLight of Ke.
+    //   - see-through (d < 1) or illum 6/7 "refraction on" -> Dielectric(Ni).
+    //   - illum 3, 5 or 8, the mirror models -> Metal, tinted by Ks, or Kd if
+    //     Ks is black. Ns sets how sharp it is, fuzz 1 - sqrt(Ns / 1000), and
+    //     a mirror without one is perfect.
+    //   - everything else -> Lambertian with map_Kd, or Kd.
+    pub fn to_material(&self, textures: &mut TextureCache) -> Result<Material, TextureError> {
+        if self.emissive.len() > 0.0 {
+            return Ok(Material::DiffuseLight { texture: Texture::Solid(self.emissive), intensity: 1.0 });
+        }
+
+        let is_transparent = self.dissolve < 1.0 || self.illum == 6 || self.illum == 7;
+        if is_transparent {
+            // Ni defaults to 1, which would make the glass invisible.
+            let ior = if self.ior > 1.0 { self.ior } else { 1.5 };
+            return Ok(Material::Dielectric(ior));
+        }
+
+        let is_mirror = matches!(self.illum, 3 | 5 | 8);
+
+        if is_mirror {
+            let tint = if self.specular.len() > 0.0 { self.specular } else { self.diffuse };
+            let fuzz = match self.shininess {
+                Some(ns) => 1.0 - (ns / 1000.0).clamp(0.0, 1.0).sqrt(),
+                None => 0.0,
+            };
+            return Ok(Material::Metal { albedo: Texture::Solid(tint), fuzz });
+        }
+
+        let texture = match &self.diffuse_map {
+            Some(path) => textures.load(path, self.diffuse_options)?,
+            None => Texture::Solid(self.diffuse),
+        };
+
+        Ok(Material::Lambertian(texture))
+    }
+}
+
+// error with the file and line, so a bad export is easy to find.
+fn parse_error(path: &Path, line: usize, message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line, message))
+}
+
+fn parse_floats(path: &Path, line: usize, args: &[&str], min: usize) -> io::Result<Vec<f32>> {
+    if args.len() < min {
+        return Err(parse_error(path, line, format!("expected at least {} numbers, found {}", min, args.len())));
+    }
+
+    args.iter()
+        .map(|a| a.parse::<f32>().map_err(|_| parse_error(path, line, format!("'{}' is not a number", a))))
+        .collect()
+}
+
+pub fn load_mtl(path: &Path) -> io::Result<Vec<MtlMaterial>> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut materials: Vec<MtlMaterial> = vec![];
+
+    for (i, raw) in source.lines().enumerate() {
+        let line = i + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        let mut words = text.split_whitespace();
+
+        let Some(keyword) = words.next() else { continue };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            let name = args.join(" ");
+            materials.push(MtlMaterial::new(&name));
+            continue;
+        }
+
+        let Some(current) = materials.last_mut() else {
+            return Err(parse_error(path, line, format!("'{}' before any newmtl", keyword)));
+        };
+
+        match keyword {
+            "Kd" | "Ks" | "Ke" => {
+                let v = parse_floats(path, line, &args, 1)?;
+                // a single value means grey.
+                let color = if v.len() >= 3 { Color::new(v[0], v[1], v[2]) } else { Color::new(v[0], v[0], v[0]) };
+
+                match keyword {
+                    "Kd" => current.diffuse = color,
+                    "Ks" => current.specular = color,
+                    _ => current.emissive = color,
+                }
+            },
+            "Ns" => current.shininess = Some(parse_floats(path, line, &args, 1)?[0]),
+            "Ni" => current.ior = parse_floats(path, line, &args, 1)?[0],
+            "d" => current.dissolve = parse_floats(path, line, &args, 1)?[0],
+            "Tr" => current.dissolve = 1.0 - parse_floats(path, line, &args, 1)?[0],
+            "illum" => {
+                current.illum = args.first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| parse_error(path, line, "illum needs an integer".to_owned()))?;
+            },
+            "map_Kd" => {
+                let (file, options) = parse_map(path, line, &args)?;
+                current.diffuse_map = Some(dir.join(file));
+                current.diffuse_options = options;
+            },
+            // Ka, bump maps, etc. have nothing to map to.
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+// a texture map statement: options, then the file name, which can have
+// spaces in it. -s and -o become the uv scale and offset, -clamp on the
+// wrap mode, the rest are skipped.
+fn parse_map(path: &Path, line: usize, args: &[&str]) -> io::Result<(String, ImageOptions)> {
+    let mut options = ImageOptions::default();
+    let mut i = 0;
+
+    // up to max numbers after an option, u then v then w.
+    let numbers = |i: &mut usize, max: usize| -> Vec<f32> {
+        let mut values = vec![];
+        while values.len() < max {
+            match args.get(*i).and_then(|a| a.parse::<f32>().ok()) {
+                Some(v) => {
+                    values.push(v);
+                    *i += 1;
+                },
+                None => break,
+            }
+        }
+        values
+    };
+
+    while let Some(option) = args.get(i).filter(|a| a.starts_with('-')) {
+        i += 1;
+
+        match *option {
+            "-s" | "-o" => {
+                let v = numbers(&mut i, 3);
+                if v.is_empty() {
+                    return Err(parse_error(path, line, format!("{} needs a number", option)));
+                }
+
+                let pair = (v[0], *v.get(1).unwrap_or(if *option == "-s" { &1.0 } else { &0.0 }));
+                if *option == "-s" { options.scale = pair; } else { options.offset = pair; }
+            },
+            "-clamp" => {
+                if args.get(i) == Some(&"on") {
+                    options.wrap = Wrap::Clamp;
+                }
+                i += 1;
+            },
+            "-t" => { numbers(&mut i, 3); },
+            "-mm" => { numbers(&mut i, 2); },
+            "-bm" | "-boost" | "-texres" => { numbers(&mut i, 1); },
+            // on/off, or a channel name.
+            "-blendu" | "-blendv" | "-cc" | "-imfchan" | "-type" => i += 1,
+            _ => return Err(parse_error(path, line, format!("unknown map option '{}'", option))),
+        }
+    }
+
+    if i >= args.len() {
+        return Err(parse_error(path, line, "a map needs a file name".to_owned()));
+    }
+
+    Ok((args[i..].join(" "), options))
+}
+
+// one corner of an obj face: position / uv / normal indices, 0 based.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// obj indices are 1 based, negative ones count back from the end.
+fn resolve_index(path: &Path, line: usize, text: &str, count: usize) -> io::Result<usize> {
+    let index: i64 = text.parse()
+        .map_err(|_| parse_error(path, line, format!("'{}' is not an index", text)))?;
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(path, line, format!("index {} is out of range (have {})", index, count)));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn parse_face_vertex(path: &Path, line: usize, text: &str, counts: (usize, usize, usize)) -> io::Result<FaceVertex> {
+    let mut parts = text.split('/');
+
+    let v = resolve_index(path, line, parts.next().unwrap_or(""), counts.0)?;
+
+    let vt = match parts.next() {
+        Some(t) if !t.is_empty() => Some(resolve_index(path, line, t, counts.1)?),
+        _ => None,
+    };
+
+    let vn = match parts.next() {
+        Some(n) if !n.is_empty() => Some(resolve_index(path, line, n, counts.2)?),
+        _ => None,
+    };
+
+    Ok((v, vt, vn))
+}
+
+// splits a polygon into triangles with ear clipping, so concave faces work.
+// returns indices into polygon. falls back to a fan if clipping gets stuck
+// (degenerate or self-intersecting polygons).
+pub fn triangulate(polygon: &[Point]) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+    if n < 3 {
+        return vec![];
+    }
+
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect::<Vec<_>>();
+    if n == 3 {
+        return fan();
+    }
+
+    // newell's method for the polygon normal, then flatten onto the
+    // plane of its two smallest components.
+    let mut normal = Point::origin();
+    for i in 0..n {
+        let (p, q) = (polygon[i], polygon[(i + 1) % n]);
+        normal = normal + Point::new(
+            (p.b - q.b) * (p.c + q.c),
+            (p.c - q.c) * (p.a + q.a),
+            (p.a - q.a) * (p.b + q.b)
+        );
+    }
+
+    let (na, nb, nc) = (normal.a.abs(), normal.b.abs(), normal.c.abs());
+    let flatten = |p: &Point| -> (f32, f32) {
+        if na >= nb && na >= nc { (p.b, p.c) }
+        else if nb >= nc { (p.c, p.a) }
+        else { (p.a, p.b) }
+    };
+
+    let points: Vec<(f32, f32)> = polygon.iter().map(flatten).collect();
+    let cross = |o: (f32, f32), a: (f32, f32), b: (f32, f32)| -> f32 {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+
+    // signed area tells us the winding in the flattened plane.
+    let area: f32 = (0..n).map(|i| cross((0.0, 0.0), points[i], points[(i + 1) % n])).sum();
+    if area == 0.0 {
+        return fan();
+    }
+    let winding = area.signum();
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = vec![];
+
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let mut clipped = false;
+
+        for i in 0..m {
+            let (ia, ib, ic) = (remaining[(i + m - 1) % m], remaining[i], remaining[(i + 1) % m]);
+            let (a, b, c) = (points[ia], points[ib], points[ic]);
+
+            // reflex corner, not an ear.
+            if cross(a, b, c) * winding <= 0.0 {
+                continue;
+            }
+
+            let contains_other = remaining.iter().any(|&j| {
+                if j == ia || j == ib || j == ic {
+                    return false;
+                }
+
+                let p = points[j];
+                cross(a, b, p) * winding >= 0.0
+                    && cross(b, c, p) * winding >= 0.0
+                    && cross(c, a, p) * winding >= 0.0
+            });
+
+            if !contains_other {
+                triangles.push([ia, ib, ic]);
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+
+        if !clipped {
+            return fan();
+        }
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// Loads an .obj file (and any .mtl files it references) as one mesh.
+// faces without a usemtl get default_material.
+// map_Kd images go through textures, so faces sharing one aren't decoded
+// again per material.
+pub fn load_obj(file_name: &str, default_material: Material, textures: &mut TextureCache) -> io::Result<Shape> {
+    let path = Path::new(file_name);
+    let source = fs::read_to_string(path)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions: Vec<Point> = vec![];
+    let mut normals: Vec<Point> = vec![];
+    let mut uvs: Vec<Point> = vec![];
+
+    let mut library: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut materials: Vec<Material> = vec![default_material];
+    let mut material_slots: HashMap<String, usize> = HashMap::new();
+    let mut current_material: usize = 0;
+
+    // faces are gathered first, then turned into shared vertices.
+    let mut faces: Vec<([FaceVertex; 3], usize)> = vec![];
+    let mut groups: Vec<MeshGroup> = vec![];
+
+    for (i, raw) in source.lines().enumerate() {
+        let line = i + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        let mut words = text.split_whitespace();
+
+        let Some(keyword) = words.next() else { continue };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(path, line, &args, 3)?;
+                positions.push(Point::new(v[0], v[1], v[2]));
+            },
+            "vn" => {
+                let v = parse_floats(path, line, &args, 3)?;
+                // a zero normal stays zero, the mesh uses the face normal there.
+                let n = Point::new(v[0], v[1], v[2]);
+                normals.push(if n.len() > 0.0 { n.unit() } else { n });
+            },
+            "vt" => {
+                let v = parse_floats(path, line, &args, 1)?;
+                uvs.push(Point::new(v[0], *v.get(1).unwrap_or(&0.0), 0.0));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(path, line, format!("a face needs 3 or more vertices, found {}", args.len())));
+                }
+
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let corners = args.iter()
+                    .map(|a| parse_face_vertex(path, line, a, counts))
+                    .collect::<io::Result<Vec<FaceVertex>>>()?;
+
+                let polygon: Vec<Point> = corners.iter().map(|c| positions[c.0]).collect();
+                for [a, b, c] in triangulate(&polygon) {
+                    faces.push(([corners[a], corners[b], corners[c]], current_material));
+                }
+            },
+            "g" | "o" => {
+                let name = if args.is_empty() { "default".to_owned() } else { args.join(" ") };
+
+                if let Some(last) = groups.last_mut() {
+                    last.end = faces.len();
+                }
+                groups.push(MeshGroup { name, start: faces.len(), end: faces.len() });
+            },
+            "mtllib" => {
+                for file in &args {
+                    for m in load_mtl(&dir.join(file))? {
+                        library.insert(m.name.clone(), m);
+                    }
+                }
+            },
+            "usemtl" => {
+                let name = args.join(" ");
+                let Some(mtl) = library.get(&name) else {
+                    return Err(parse_error(path, line, format!("material '{}' is not in any mtllib", name)));
+                };
+
+                current_material = match material_slots.get(&name) {
+                    Some(&slot) => slot,
+                    None => {
+                        // keeps the error's kind, a missing image is still NotFound.
+                        let material = mtl.to_material(textures).map_err(|e| {
+                            let e = Error::from(e);
+                            Error::new(e.kind(), format!("{}:{}: {}", path.display(), line, e))
+                        })?;
+                        materials.push(material);
+                        material_slots.insert(name, materials.len() - 1);
+                        materials.len() - 1
+                    },
+                };
+            },
+            // smoothing groups, curves, etc.
+            _ => {}
+        }
+    }
+
+    if let Some(last) = groups.last_mut() {
+        last.end = faces.len();
+    }
+    // drop empty groups, e.g. an "o" immediately followed by a "g".
+    groups.retain(|g| g.end > g.start);
+
+    // normals/uvs are all or nothing per mesh.
+    let has_normals = faces.iter().all(|(f, _)| f.iter().all(|c| c.2.is_some()));
+    let has_uvs = faces.iter().all(|(f, _)| f.iter().all(|c| c.1.is_some()));
+
+    let mut data = MeshData::default();
+    let mut vertex_ids: HashMap<FaceVertex, usize> = HashMap::new();
+
+    for (face, material) in &faces {
+        let mut triangle = [0usize; 3];
+
+        for (k, corner) in face.iter().enumerate() {
+            triangle[k] = *vertex_ids.entry(*corner).or_insert_with(|| {
+                data.positions.push(positions[corner.0]);
+                if has_normals { data.normals.push(normals[corner.2.unwrap()]); }
+                if has_uvs { data.uvs.push(uvs[corner.1.unwrap()]); }
+                data.positions.len() - 1
+            });
+        }
+
+        data.indices.push(triangle);
+        data.material_ids.push(*material);
+    }
+
+    data.groups = groups;
+
+    println!(
+        "Loaded {}: {} triangles, {} vertices, {} materials",
+        file_name, data.indices.len(), data.positions.len(), materials.len() - 1
+    );
+
+    Ok(Shape::mesh(Mesh::from_shared(Arc::new(data), materials)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::ray::Ray;
+    use crate::util::hittable::Hittable;
+
+    // writes files into a fresh directory under the system's temp dir.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rs-raycast-obj-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn load(test: &str, files: &[(&str, &str)]) -> io::Result<Mesh> {
+        let dir = write_files(test, files);
+        let grey = Material::Lambertian(Texture::Solid(Color::new(0.5, 0.5, 0.5)));
+        let shape = load_obj(&dir.join(files[0].0).to_string_lossy(), grey, &mut TextureCache::new(false));
+        fs::remove_dir_all(&dir).unwrap();
+
+        match shape? {
+            Shape::Mesh(mesh) => Ok(mesh),
+            _ => panic!("load_obj didn't make a mesh"),
+        }
+    }
+
+    fn area(polygon: &[Point], triangles: &[[usize; 3]]) -> f32 {
+        triangles.iter()
+            .map(|[a, b, c]| {
+                let (p, q, r) = (polygon[*a], polygon[*b], polygon[*c]);
+                (q + p.scalar_mul(-1.0)).cross(r + p.scalar_mul(-1.0)).len() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn convex_faces_become_fans() {
+        let pentagon: Vec<Point> = (0..5)
+            .map(|i| {
+                let angle = i as f32 * 2.0 * std::f32::consts::PI / 5.0;
+                Point::new(angle.cos(), angle.sin(), 0.0)
+            })
+            .collect();
+
+        let triangles = triangulate(&pentagon);
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles.iter().all(|t| t.contains(&0) || t.contains(&2)));
+
+        let expected = 2.5 * (2.0 * std::f32::consts::PI / 5.0).sin();
+        assert!((area(&pentagon, &triangles) - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn concave_faces_stay_inside() {
+        // an arrow head, 3 is the reflex corner. a fan from 0 would cover
+        // area outside the polygon.
+        let arrow = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 1.0, 0.0),
+            Point::new(0.0, 2.0, 0.0),
+            Point::new(0.5, 1.0, 0.0),
+        ];
+
+        let triangles = triangulate(&arrow);
+        assert_eq!(triangles.len(), 2);
+        assert!((area(&arrow, &triangles) - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn degenerate_faces_fall_back_to_a_fan() {
+        let line = [Point::origin(), Point::new(1.0, 0.0, 0.0), Point::new(2.0, 0.0, 0.0), Point::new(3.0, 0.0, 0.0)];
+        assert_eq!(triangulate(&line), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn indices_count_from_one_or_back_from_the_end() {
+        let path = Path::new("test.obj");
+
+        assert_eq!(resolve_index(path, 1, "1", 4).unwrap(), 0);
+        assert_eq!(resolve_index(path, 1, "-1", 4).unwrap(), 3);
+        assert_eq!(resolve_index(path, 1, "-4", 4).unwrap(), 0);
+        assert!(resolve_index(path, 1, "0", 4).is_err());
+        assert!(resolve_index(path, 1, "-5", 4).is_err());
+        assert!(resolve_index(path, 1, "5", 4).is_err());
+    }
+
+    #[test]
+    fn loads_a_quad_with_negative_indices() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf -4/-4 -3/-3 -2/-2 -1/-1\n";
+        let mesh = load("negative", &[("quad.obj", obj)]).unwrap();
+
+        assert_eq!(mesh.data.indices.len(), 2);
+        assert_eq!(mesh.data.positions.len(), 4);
+        assert_eq!(mesh.data.uvs.len(), 4);
+    }
+
+    #[test]
+    fn zero_normals_use_the_face_normal() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 0\nf 1//1 2//1 3//1\n";
+        let mesh = load("zero-normal", &[("tri.obj", obj)]).unwrap();
+
+        let hit = mesh.hit(Ray::new(Point::new(0.2, 0.2, 1.0), Point::new(0.0, 0.0, -1.0))).unwrap();
+        assert!((hit.normal.c - 1.0).abs() < 1e-5, "{}", hit.normal);
+    }
+
+    #[test]
+    fn map_options_and_spaces_in_file_names() {
+        let path = Path::new("test.mtl");
+        let args = ["-s", "2", "3", "1", "-o", "-0.5", "-clamp", "on", "-bm", "0.5", "old", "brick.png"];
+        let (file, options) = parse_map(path, 1, &args).unwrap();
+
+        assert_eq!(file, "old brick.png");
+        assert_eq!(options.scale, (2.0, 3.0));
+        assert_eq!(options.offset, (-0.5, 0.0));
+        assert_eq!(options.wrap, Wrap::Clamp);
+
+        assert!(parse_map(path, 1, &["-s", "2"]).is_err());
+        assert!(parse_map(path, 1, &["-q", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn mirrors_come_from_illum_and_ns_sets_their_fuzz() {
+        let mtl = "newmtl matte\nKd 1 0 0\nNs 900\n\
+                   newmtl polished\nKs 0.9 0.9 0.9\nNs 900\nillum 3\n\
+                   newmtl brushed\nKs 0.9 0.9 0.9\nNs 10\nillum 3\n\
+                   newmtl mirror\nKs 0.9 0.9 0.9\nillum 3\n";
+        let dir = write_files("illum", &[("m.mtl", mtl)]);
+        let materials = load_mtl(&dir.join("m.mtl")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut textures = TextureCache::new(false);
+        let mut fuzz = |m: &MtlMaterial| match m.to_material(&mut textures).unwrap() {
+            Material::Metal { fuzz, .. } => Some(fuzz),
+            _ => None,
+        };
+
+        // a highlight alone doesn't make a mirror, illum does.
+        assert_eq!(fuzz(&materials[0]), None);
+        let polished = fuzz(&materials[1]).unwrap();
+        let brushed = fuzz(&materials[2]).unwrap();
+        assert!((polished - (1.0 - 0.9f32.sqrt())).abs() < 1e-5);
+        assert!(polished < 0.1 && brushed > 0.8);
+        assert_eq!(fuzz(&materials[3]), Some(0.0));
+    }
+
+    #[test]
+    fn missing_maps_stay_not_found() {
+        let obj = "mtllib m.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl brick\nf 1 2 3\n";
+        let mtl = "newmtl brick\nmap_Kd nowhere.png\n";
+        let error = load("missing-map", &[("m.obj", obj), ("m.mtl", mtl)]).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert!(error.to_string().contains("m.obj:5: can't read image"));
+    }
+}
@@ -0,0 +1,94 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Error, Write};
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::openexr::OpenExrEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::{ColorType, ImageEncoder, ImageError, Rgb};
+
+use crate::util::framebuffer::Framebuffer;
+
+// file formats a framebuffer can be written as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Ppm, // binary P6
+    Exr, // 32-bit float, keeps values above 1
+    Hdr, // radiance rgbe, keeps values above 1
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static str = "png, jpg, ppm, exr, hdr";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "ppm" => Some(OutputFormat::Ppm),
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Hdr),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        OutputFormat::from_name(extension)
+    }
+}
+
+fn to_io_error(e: ImageError) -> Error {
+    match e {
+        ImageError::IoError(e) => e,
+        e => Error::other(e),
+    }
+}
+
+pub fn write(framebuffer: &Framebuffer, file_name: &str, format: OutputFormat) -> io::Result<()> {
+    let file = File::create(file_name)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", file_name, e)))?;
+    let mut writer = BufWriter::new(file);
+
+    let (width, height) = (framebuffer.width, framebuffer.height);
+
+    let result = match format {
+        OutputFormat::Png => PngEncoder::new(&mut writer)
+            .write_image(&framebuffer.to_rgb8(), width, height, ColorType::Rgb8),
+        OutputFormat::Jpeg => JpegEncoder::new_with_quality(&mut writer, 95)
+            .write_image(&framebuffer.to_rgb8(), width, height, ColorType::Rgb8),
+        OutputFormat::Ppm => PnmEncoder::new(&mut writer)
+            .with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary))
+            .write_image(&framebuffer.to_rgb8(), width, height, ColorType::Rgb8),
+        OutputFormat::Exr => {
+            // the encoder takes raw bytes, even for float images.
+            let data: Vec<u8> = framebuffer.to_rgb32f()
+                .iter()
+                .flat_map(|f| f.to_ne_bytes())
+                .collect();
+
+            OpenExrEncoder::new(&mut writer).write_image(&data, width, height, ColorType::Rgb32F)
+        },
+        OutputFormat::Hdr => {
+            let data: Vec<Rgb<f32>> = framebuffer.pixels.iter().map(|c| Rgb([c.a, c.b, c.c])).collect();
+            HdrEncoder::new(&mut writer).encode(&data, width as usize, height as usize)
+        },
+    };
+
+    result.map_err(to_io_error)?;
+    // dropping the writer would flush too, but swallow any error doing it.
+    writer.flush()
+}
+
+// writes next to file_name first and then moves it over, so the file is
+// never left half written if the render is stopped while writing.
+pub fn write_replacing(framebuffer: &Framebuffer, file_name: &str, format: OutputFormat) -> io::Result<()> {
+    let partial = format!("{}.part", file_name);
+    write(framebuffer, &partial, format)?;
+
+    fs::rename(&partial, file_name)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", file_name, e)))
+}
@@ -0,0 +1,356 @@
+use std::f32::consts::PI;
+
+use crate::math::sampling;
+use crate::math::vector::*;
+use crate::util::hittable::RayCollision;
+use crate::util::texture::Texture;
+
+/*
+ *  Metallic/roughness material, the kind most look-dev tools export: a
+ *  diffuse base under a GGX microfacet specular layer (walter et al. 2007)
+ *  with height-correlated smith shadowing. Metallic fades the diffuse out
+ *  and tints the specular with the base colour, or with the exact fresnel
+ *  of a complex ior when one is given.
+ *
+ *  Directions are local to the surface in here, z along the normal, with
+ *  outgoing pointing back where the ray came from.
+ */
+
+#[derive(Clone, Debug)]
+pub struct Pbr {
+    pub base_color: Texture,
+    pub roughness: Texture, // red channel, 0 (polished) to 1.
+    pub metallic: Texture, // red channel, 0 (plastic, wood) to 1 (metal).
+    pub ior: f32, // of the non-metal part, 1.5 reflects 4% head on.
+    pub conductor: Option<(Color, Color)>, // complex ior (eta, k) of the metal part.
+}
+
+// the textures looked up at one point.
+struct Surface {
+    base: Color,
+    metallic: f32,
+    alpha: f32, // ggx width, roughness squared.
+    f0: f32, // head on reflectance of the non-metal part.
+}
+
+impl Pbr {
+    fn surface(&self, collision: &RayCollision) -> Surface {
+        let roughness = self.roughness.at(collision).a.clamp(0.0, 1.0);
+        let r0 = (self.ior - 1.0) / (self.ior + 1.0);
+
+        Surface {
+            base: self.base_color.at(collision),
+            metallic: self.metallic.at(collision).a.clamp(0.0, 1.0),
+            // a little roughness always, a perfect mirror would divide by 0.
+            alpha: (roughness * roughness).max(1e-3),
+            f0: r0 * r0,
+        }
+    }
+
+    // reflectance of the metal part at cosine between the light and the
+    // microfacet.
+    fn metal_fresnel(&self, surface: &Surface, cosine: f32) -> Color {
+        match self.conductor {
+            Some((eta, k)) => Color::new(
+                conductor_fresnel(cosine, eta.a, k.a),
+                conductor_fresnel(cosine, eta.b, k.b),
+                conductor_fresnel(cosine, eta.c, k.c),
+            ),
+            None => {
+                let white = Color::new(1.0, 1.0, 1.0);
+                surface.base + (white + surface.base.scalar_mul(-1.0)).scalar_mul(schlick_weight(cosine))
+            },
+        }
+    }
+
+    // chance of sampling the specular lobe rather than the diffuse one,
+    // from how much each reflects head on.
+    fn specular_chance(&self, surface: &Surface) -> f32 {
+        let metal = average(self.metal_fresnel(surface, 1.0));
+        let specular = surface.f0 * (1.0 - surface.metallic) + metal * surface.metallic;
+        let diffuse = (1.0 - surface.metallic) * (1.0 - surface.f0) * average(surface.base);
+
+        if specular + diffuse <= 0.0 {
+            return 1.0;
+        }
+
+        // kept from getting too small, grazing highlights reflect far more
+        // than the head on value suggests.
+        (specular / (specular + diffuse)).max(if diffuse > 0.0 { 0.25 } else { 1.0 })
+    }
+
+    // the bsdf times the cosine term.
+    pub fn eval(&self, collision: &RayCollision, outgoing: Point, incoming: Point) -> Color {
+        let (wo, wi) = local(collision, outgoing, incoming);
+        if wo.c <= 0.0 || wi.c <= 0.0 {
+            return Color::origin();
+        }
+
+        let surface = self.surface(collision);
+        let half = (wo + wi).unit();
+        let cosine = Vector3::dot(&wi, &half).max(0.0);
+
+        let dielectric = |cosine: f32| surface.f0 + (1.0 - surface.f0) * schlick_weight(cosine);
+        let fresnel = Color::new(1.0, 1.0, 1.0).scalar_mul(dielectric(cosine) * (1.0 - surface.metallic))
+            + self.metal_fresnel(&surface, cosine).scalar_mul(surface.metallic);
+
+        let specular = fresnel.scalar_mul(ggx_d(half, surface.alpha) * smith_g(wo, wi, surface.alpha) / (4.0 * wo.c));
+
+        // the diffuse base only gets light the coating lets through, on the
+        // way in and on the way out, so grazing angles don't reflect more
+        // than arrives.
+        let transmitted = (1.0 - dielectric(wo.c)) * (1.0 - dielectric(wi.c));
+        let diffuse = surface.base.scalar_mul((1.0 - surface.metallic) * transmitted * wi.c / PI);
+
+        specular + diffuse
+    }
+
+    pub fn pdf(&self, collision: &RayCollision, outgoing: Point, incoming: Point) -> f32 {
+        let (wo, wi) = local(collision, outgoing, incoming);
+        if wo.c <= 0.0 || wi.c <= 0.0 {
+            return 0.0;
+        }
+
+        let surface = self.surface(collision);
+        let chance = self.specular_chance(&surface);
+        let half = (wo + wi).unit();
+
+        // visible normals pdf, turned from the half vector into wi.
+        let specular = smith_g1(wo, surface.alpha) * ggx_d(half, surface.alpha) / (4.0 * wo.c);
+        let diffuse = wi.c / PI;
+
+        chance * specular + (1.0 - chance) * diffuse
+    }
+
+    // a direction for light to arrive from, None if outgoing is behind
+    // the surface. u picks the lobe, u2 the direction within it.
+    pub fn sample(&self, collision: &RayCollision, outgoing: Point, u: f32, u2: (f32, f32)) -> Option<Point> {
+        let wo = collision.normal.from_frame(outgoing.unit());
+        if wo.c <= 0.0 {
+            return None;
+        }
+
+        let surface = self.surface(collision);
+        let wi = if u < self.specular_chance(&surface) {
+            let half = sample_visible_normal(wo, surface.alpha, u2);
+            (half.scalar_mul(2.0 * Vector3::dot(&wo, &half)) + wo.scalar_mul(-1.0)).unit()
+        } else {
+            sampling::cosine_hemisphere(u2)
+        };
+
+        Some(collision.normal.to_frame(wi))
+    }
+}
+
+fn local(collision: &RayCollision, outgoing: Point, incoming: Point) -> (Point, Point) {
+    (collision.normal.from_frame(outgoing.unit()), collision.normal.from_frame(incoming.unit()))
+}
+
+fn average(c: Color) -> f32 {
+    (c.a + c.b + c.c) / 3.0
+}
+
+// (1 - cos)^5, how far schlick's approximation moves towards white.
+fn schlick_weight(cosine: f32) -> f32 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+// exact fresnel reflectance from air onto a conductor with complex ior
+// eta + ik, averaged over both polarisations.
+fn conductor_fresnel(cosine: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = (cosine * cosine).clamp(0.0, 1.0);
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2b2 + cos2;
+    let t2 = 2.0 * cosine * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// how much of the surface has its microfacets facing half.
+fn ggx_d(half: Point, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let cos2 = half.c * half.c;
+    let denominator = cos2 * (alpha2 - 1.0) + 1.0;
+
+    alpha2 / (PI * denominator * denominator)
+}
+
+// smith's lambda, the hidden share of microfacets seen from w.
+fn smith_lambda(w: Point, alpha: f32) -> f32 {
+    let cos2 = w.c * w.c;
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+
+    ((1.0 + alpha * alpha * tan2).sqrt() - 1.0) / 2.0
+}
+
+fn smith_g1(w: Point, alpha: f32) -> f32 {
+    1.0 / (1.0 + smith_lambda(w, alpha))
+}
+
+// how much of the surface is seen from both wo and wi. taller microfacets
+// hide more from both sides at once (height-correlated).
+fn smith_g(wo: Point, wi: Point, alpha: f32) -> f32 {
+    1.0 / (1.0 + smith_lambda(wo, alpha) + smith_lambda(wi, alpha))
+}
+
+// a microfacet normal as seen from wo (heitz 2018), skipping the ones
+// that face away or are hidden, so no samples are wasted.
+fn sample_visible_normal(wo: Point, alpha: f32, u: (f32, f32)) -> Point {
+    // stretch into the configuration where the microfacets are a hemisphere.
+    let view = Point::new(alpha * wo.a, alpha * wo.b, wo.c).unit();
+
+    let length2 = view.a * view.a + view.b * view.b;
+    let t1 = if length2 > 0.0 {
+        Point::new(-view.b, view.a, 0.0).scalar_mul(1.0 / length2.sqrt())
+    } else {
+        Point::new(1.0, 0.0, 0.0)
+    };
+    let t2 = view.cross(t1);
+
+    // a disk point, squashed towards the part of the hemisphere in view.
+    let (p1, p2) = sampling::uniform_disk(u);
+    let s = 0.5 * (1.0 + view.c);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+    let pz = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+    let normal = t1.scalar_mul(p1) + t2.scalar_mul(p2) + view.scalar_mul(pz);
+
+    // and unstretch.
+    Point::new(alpha * normal.a, alpha * normal.b, normal.c.max(1e-6)).unit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::ray::Ray;
+    use crate::math::rng::Pcg32;
+    use crate::util::material::Material;
+
+    fn pbr(roughness: f32, metallic: f32) -> Pbr {
+        Pbr {
+            base_color: Texture::Solid(Color::new(1.0, 1.0, 1.0)),
+            roughness: Texture::Solid(Color::new(roughness, roughness, roughness)),
+            metallic: Texture::Solid(Color::new(metallic, metallic, metallic)),
+            ior: 1.5,
+            conductor: None,
+        }
+    }
+
+    // a hit on the z = 0 plane from above, normal up.
+    fn flat(pbr: &Pbr) -> RayCollision {
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+        RayCollision::new(ray, Point::new(0.0, 0.0, 1.0), 1.0, Material::Pbr(pbr.clone()))
+    }
+
+    // leaving at theta degrees from the normal.
+    fn outgoing(theta: f32) -> Point {
+        let theta = theta.to_radians();
+        Point::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    // the share of light from outgoing that's reflected, estimated with
+    // the material's own sampling.
+    fn reflectance(pbr: &Pbr, outgoing: Point, rng: &mut Pcg32) -> f32 {
+        let collision = flat(pbr);
+        let n = 20000;
+        let mut total = 0.0;
+
+        for _ in 0..n {
+            let u = (rng.next_f32(), (rng.next_f32(), rng.next_f32()));
+            if let Some(incoming) = pbr.sample(&collision, outgoing, u.0, u.1) {
+                let pdf = pbr.pdf(&collision, outgoing, incoming);
+                if pdf > 0.0 {
+                    total += average(pbr.eval(&collision, outgoing, incoming)) / pdf;
+                }
+            }
+        }
+
+        total / n as f32
+    }
+
+    #[test]
+    fn samples_agree_with_eval_and_pdf() {
+        let mut rng = Pcg32::new(1, 0);
+
+        for (roughness, metallic) in [(0.2, 0.0), (0.5, 0.5), (0.8, 1.0)] {
+            let pbr = pbr(roughness, metallic);
+            let collision = flat(&pbr);
+            let wo = outgoing(40.0);
+
+            for _ in 0..2000 {
+                let u = (rng.next_f32(), (rng.next_f32(), rng.next_f32()));
+                let Some(wi) = pbr.sample(&collision, wo, u.0, u.1) else { continue };
+
+                // microfacets can send a sample under the surface, which
+                // scatter absorbs. anything above has to be possible.
+                if wi.c > 1e-4 {
+                    let pdf = pbr.pdf(&collision, wo, wi);
+                    assert!(pdf > 0.0 && pdf.is_finite(), "pdf {} for {}", pdf, wi);
+                    assert!(average(pbr.eval(&collision, wo, wi)).is_finite());
+                }
+            }
+
+            // sampling by pdf and sampling evenly see the same reflectance,
+            // which only holds if pdf is the density sample really has.
+            let sampled = reflectance(&pbr, wo, &mut rng);
+            let mut even = 0.0;
+            let n = 200000;
+            for _ in 0..n {
+                let wi = sampling::uniform_sphere((rng.next_f32(), rng.next_f32()));
+                even += average(pbr.eval(&collision, wo, wi)) * 4.0 * PI;
+            }
+            even /= n as f32;
+
+            assert!((sampled - even).abs() < 0.03, "roughness {}: sampled {} even {}", roughness, sampled, even);
+        }
+    }
+
+    #[test]
+    fn white_metal_never_gains_energy() {
+        let mut rng = Pcg32::new(2, 0);
+
+        for roughness in [0.1, 1.0] {
+            for theta in [0.0, 45.0, 80.0] {
+                // single scattering loses what bounces between microfacets,
+                // most of it at roughness 1, but must never add any.
+                let r = reflectance(&pbr(roughness, 1.0), outgoing(theta), &mut rng);
+                assert!(r <= 1.0 && r > 0.2, "roughness {} at {}: {}", roughness, theta, r);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_roughness_stays_finite() {
+        let mut rng = Pcg32::new(3, 0);
+
+        for metallic in [0.0, 1.0] {
+            let pbr = pbr(0.0, metallic);
+            let collision = flat(&pbr);
+
+            for theta in [0.0, 30.0, 89.0] {
+                let wo = outgoing(theta);
+                for _ in 0..200 {
+                    let u = (rng.next_f32(), (rng.next_f32(), rng.next_f32()));
+                    let Some(wi) = pbr.sample(&collision, wo, u.0, u.1) else { continue };
+
+                    let (f, pdf) = (pbr.eval(&collision, wo, wi), pbr.pdf(&collision, wo, wi));
+                    assert!(f.a.is_finite() && f.b.is_finite() && f.c.is_finite() && pdf.is_finite());
+                    if pdf > 0.0 {
+                        assert!((average(f) / pdf).is_finite());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use crate::math::rng::Pcg32;
+
+/*
+ *  Where the random numbers for a pixel sample come from. Every call to
+ *  next_1d or next_2d uses up a dimension: the camera takes the first few
+ *  (pixel jitter, lens, time), then each bounce takes more. Samplers other
+ *  than independent spread a pixel's samples out evenly in each dimension,
+ *  so images converge with fewer samples.
+ */
+
+pub trait Sampler {
+    // begin sample index (of samples) of pixel (x, y), back at dimension 0.
+    fn start(&mut self, x: u32, y: u32, index: u32);
+
+    fn next_1d(&mut self) -> f32;
+
+    fn next_2d(&mut self) -> (f32, f32);
+}
+
+// what every sampler keeps track of.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SampleState {
+    pub samples: u32, // samples per pixel.
+    pub seed: u64, // changes every scramble and stream, for different noise patterns.
+    pixel: u32, // hash of the pixel and seed.
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        let seed = hash(self.seed as u32 ^ hash((self.seed >> 32) as u32));
+        self.pixel = hash(x ^ hash(y ^ seed));
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // a hash unique to this pixel, dimension and purpose.
+    fn scramble(&self, salt: u32) -> u32 {
+        hash(self.pixel ^ hash(self.dimension ^ hash(salt)))
+    }
+
+    fn advance(&mut self) -> u32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        dimension
+    }
+}
+
+// Wrapper sampler type, so the render settings can stay Copy and have no
+// need for dyn Sampler. picked with --sampler or image { sampler }.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent(Independent),
+    Stratified(Stratified),
+    Halton(Halton),
+    Sobol(Sobol),
+}
+
+impl Default for SamplerKind {
+    fn default() -> Self {
+        SamplerKind::Sobol(Sobol::default())
+    }
+}
+
+impl SamplerKind {
+    pub const NAMES: &'static str = "independent, stratified, halton, sobol";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "independent" | "random" => Some(SamplerKind::Independent(Independent::default())),
+            "stratified" => Some(SamplerKind::Stratified(Stratified::default())),
+            "halton" => Some(SamplerKind::Halton(Halton::default())),
+            "sobol" => Some(SamplerKind::Sobol(Sobol::default())),
+            _ => None,
+        }
+    }
+
+    // a copy set up for a render of samples per pixel.
+    pub fn prepare(&self, samples: u32, seed: u64) -> Self {
+        let mut sampler = *self;
+        let state = match &mut sampler {
+            SamplerKind::Independent(s) => &mut s.state,
+            SamplerKind::Stratified(s) => &mut s.state,
+            SamplerKind::Halton(s) => &mut s.state,
+            SamplerKind::Sobol(s) => &mut s.state,
+        };
+
+        state.samples = samples.max(1);
+        state.seed = seed;
+        sampler
+    }
+}
+
+impl Sampler for SamplerKind {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        match self {
+            SamplerKind::Independent(s) => s.start(x, y, index),
+            SamplerKind::Stratified(s) => s.start(x, y, index),
+            SamplerKind::Halton(s) => s.start(x, y, index),
+            SamplerKind::Sobol(s) => s.start(x, y, index),
+        }
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        match self {
+            SamplerKind::Independent(s) => s.next_1d(),
+            SamplerKind::Stratified(s) => s.next_1d(),
+            SamplerKind::Halton(s) => s.next_1d(),
+            SamplerKind::Sobol(s) => s.next_1d(),
+        }
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        match self {
+            SamplerKind::Independent(s) => s.next_2d(),
+            SamplerKind::Stratified(s) => s.next_2d(),
+            SamplerKind::Halton(s) => s.next_2d(),
+            SamplerKind::Sobol(s) => s.next_2d(),
+        }
+    }
+}
+
+/*
+ *  Plain uniform random numbers, nothing spread out. each pixel sample
+ *  gets its own generator, seeded by the pixel, on a stream picked by the
+ *  sample index.
+ */
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Independent {
+    pub state: SampleState,
+    rng: Pcg32,
+}
+
+impl Sampler for Independent {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+        self.rng = Pcg32::new(self.state.pixel as u64 | (self.state.seed << 32), index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.rng.next_f32(), self.rng.next_f32())
+    }
+}
+
+/*
+ *  Jittered strata. in 1d each sample gets its own slice of [0, 1); in 2d
+ *  samples are correlated multi-jittered (kensler 2013), stratified on the
+ *  grid and on each axis, for any sample count. strata are shuffled per
+ *  pixel and dimension so dimensions don't line up with each other.
+ */
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stratified {
+    pub state: SampleState,
+}
+
+impl Sampler for Stratified {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let n = self.state.samples;
+        let p = self.state.scramble(0x1D);
+        self.state.advance();
+
+        let stratum = permute(self.state.index % n, n, p);
+        (stratum as f32 + to_unit(hash(self.state.index ^ p))) / n as f32
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let n = self.state.samples;
+        let p = self.state.scramble(0x2D);
+        self.state.advance();
+
+        // m columns by rows rows, at least n cells.
+        let m = ((n as f32).sqrt() as u32).max(1);
+        let rows = n.div_ceil(m);
+
+        let s = permute(self.state.index % n, n, p.wrapping_mul(0x51633e2d));
+        let sx = permute(s % m, m, p.wrapping_mul(0x68bc21eb));
+        let sy = permute(s / m, rows, p.wrapping_mul(0x02e5be93));
+        let jx = to_unit(hash(s ^ p.wrapping_mul(0x967a889b)));
+        let jy = to_unit(hash(s ^ p.wrapping_mul(0x368cc8b7)));
+
+        let x = (sx as f32 + (sy as f32 + jx) / rows as f32) / m as f32;
+        let y = (s as f32 + jy) / n as f32;
+
+        (x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON))
+    }
+}
+
+/*
+ *  Halton sequence: the radical inverse of the sample index in a different
+ *  prime base per dimension. the digits are shuffled by a random
+ *  permutation per pixel, dimension and digit (random digit scrambling),
+ *  so neighbouring pixels don't share noise, and the large bases don't
+ *  march through [0, 1) in step with each other at low sample counts.
+ *  dimensions past the prime table fall back to hashed random numbers.
+ */
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Halton {
+    pub state: SampleState,
+}
+
+impl Sampler for Halton {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let seed = self.state.scramble(0x4A);
+        let dimension = self.state.advance() as usize;
+
+        match PRIMES.get(dimension) {
+            Some(&base) => scrambled_radical_inverse(self.state.index, base, seed),
+            None => to_unit(hash(self.state.index ^ self.state.scramble(0x4B))),
+        }
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+// the index's digits mirrored around the point, each put through its own
+// permutation. the zeros past the last digit are permuted too, so it
+// carries on until they're too small for an f32 to show.
+fn scrambled_radical_inverse(mut index: u32, base: u32, seed: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let (mut reversed, mut scale) = (0.0f64, inv_base);
+    let mut digit = 0;
+
+    while scale > 1e-8 {
+        reversed += permute(index % base, base, hash(seed ^ digit)) as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+        digit += 1;
+    }
+
+    (reversed as f32).min(ONE_MINUS_EPSILON)
+}
+
+/*
+ *  Sobol (0, 2)-sequence, its first two dimensions, padded out to as many
+ *  dimensions as needed by scrambling each pair differently. owen
+ *  scrambling keeps the sequence well spread while making every pixel's
+ *  noise different (burley 2020).
+ */
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Sobol {
+    pub state: SampleState,
+}
+
+impl Sampler for Sobol {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.next_2d().0
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let seed = self.state.scramble(0x50);
+        self.state.advance();
+
+        let index = nested_uniform_scramble(self.state.index, seed);
+        let (x, y) = sobol_2d(index);
+
+        (
+            to_unit(nested_uniform_scramble(x, hash(seed ^ 0x1))),
+            to_unit(nested_uniform_scramble(y, hash(seed ^ 0x2))),
+        )
+    }
+}
+
+// both dimensions as 32-bit fractions. the first is van der corput, the
+// second's direction numbers follow v = v ^ (v >> 1).
+fn sobol_2d(mut index: u32) -> (u32, u32) {
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut v = 1u32 << 31;
+    let mut bit = 31;
+
+    while index != 0 {
+        if index & 1 == 1 {
+            x ^= 1 << bit;
+            y ^= v;
+        }
+
+        index >>= 1;
+        bit -= 1;
+        v ^= v >> 1;
+    }
+
+    (x, y)
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/*
+ *  Hashing helpers.
+ */
+
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// lowbias32 (wellons), well mixed and cheap.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+// top 24 bits as a float in [0, 1).
+fn to_unit(x: u32) -> f32 {
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+// i's place in a random shuffle of 0..l picked by p (kensler 2013).
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < l {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % l
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all(samples: u32) -> Vec<SamplerKind> {
+        ["independent", "stratified", "halton", "sobol"].iter()
+            .map(|name| SamplerKind::from_name(name).unwrap().prepare(samples, 7))
+            .collect()
+    }
+
+    // the first dimensions of every sample of pixel (x, y), 2 per next_2d.
+    fn pixel(sampler: &mut SamplerKind, x: u32, y: u32, samples: u32, dimensions: u32) -> Vec<Vec<f32>> {
+        (0..samples).map(|i| {
+            sampler.start(x, y, i);
+            (0..dimensions).flat_map(|_| { let (a, b) = sampler.next_2d(); [a, b] }).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn values_are_in_the_unit_interval() {
+        for mut sampler in all(13) {
+            for (x, y) in [(0, 0), (5, 9), (1023, 767)] {
+                for i in 0..13 {
+                    sampler.start(x, y, i);
+                    for _ in 0..40 {
+                        let (a, b) = sampler.next_2d();
+                        let c = sampler.next_1d();
+                        assert!([a, b, c].iter().all(|v| (0.0..1.0).contains(v)), "{:?}: {} {} {}", sampler, a, b, c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn one_sample_per_stratum_in_every_dimension() {
+        let n = 16;
+        for name in ["stratified", "sobol"] {
+            let mut sampler = SamplerKind::from_name(name).unwrap().prepare(n, 3);
+
+            for (x, y) in [(0, 0), (17, 4)] {
+                let samples = pixel(&mut sampler, x, y, n, 12);
+
+                for dimension in 0..24 {
+                    let mut strata: Vec<u32> = samples.iter().map(|s| (s[dimension] * n as f32) as u32).collect();
+                    strata.sort();
+                    assert_eq!(strata, (0..n).collect::<Vec<_>>(), "{} dimension {}", name, dimension);
+                }
+
+                // and the same through next_1d.
+                let strata = (0..n).map(|i| {
+                    sampler.start(x, y, i);
+                    (sampler.next_1d() * n as f32) as u32
+                });
+                let mut strata: Vec<u32> = strata.collect();
+                strata.sort();
+                assert_eq!(strata, (0..n).collect::<Vec<_>>(), "{} next_1d", name);
+            }
+        }
+
+        // stratified manages it for counts that aren't powers of two too.
+        let mut sampler = SamplerKind::from_name("stratified").unwrap().prepare(12, 3);
+        let samples = pixel(&mut sampler, 2, 2, 12, 6);
+        for dimension in 0..12 {
+            let mut strata: Vec<u32> = samples.iter().map(|s| (s[dimension] * 12.0) as u32).collect();
+            strata.sort();
+            assert_eq!(strata, (0..12).collect::<Vec<_>>(), "dimension {}", dimension);
+        }
+    }
+
+    #[test]
+    fn neighbouring_pixels_are_scrambled_differently() {
+        for mut sampler in all(16) {
+            let here = pixel(&mut sampler, 10, 10, 16, 4);
+
+            for (x, y) in [(11, 10), (10, 11), (9, 10)] {
+                assert_ne!(here, pixel(&mut sampler, x, y, 16, 4), "{:?} at {}, {}", sampler, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn spread_out_samplers_converge_faster() {
+        // mean of x * y over the unit square is 1/4. rms error of 16 sample
+        // estimates over many pixels, in the pair of dimensions given.
+        let n = 16;
+        let rms = |sampler: &mut SamplerKind, pair: usize| {
+            let mut total = 0.0;
+            for p in 0..256 {
+                let samples = pixel(sampler, p % 16, p / 16, n, pair as u32 + 1);
+                let estimate = samples.iter().map(|s| s[2 * pair] * s[2 * pair + 1]).sum::<f32>() / n as f32;
+                total += (estimate - 0.25) * (estimate - 0.25);
+            }
+            (total / 256.0).sqrt()
+        };
+
+        // pixel jitter, and two pairs into the bounces.
+        let mut samplers = all(n);
+        for pair in [0, 2, 5] {
+            let independent = rms(&mut samplers[0], pair);
+            let [stratified, halton, sobol] = [1, 2, 3].map(|i| rms(&mut samplers[i], pair));
+
+            assert!(stratified < independent * 0.5, "stratified, pair {}: {} against {}", pair, stratified, independent);
+            assert!(sobol < independent * 0.5, "sobol, pair {}: {} against {}", pair, sobol, independent);
+            // halton's big bases can't spread 16 samples over every digit,
+            // so further in it's better, but not by as much.
+            let factor = if pair == 0 { 0.5 } else { 0.9 };
+            assert!(halton < independent * factor, "halton, pair {}: {} against {}", pair, halton, independent);
+        }
+    }
+}
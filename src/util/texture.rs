@@ -1,11 +1,54 @@
-use image::{io::Reader as ImageReader, GenericImageView, DynamicImage};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::{io::Reader as ImageReader, DynamicImage, ImageError};
 
 use crate::Color;
+use crate::math::lerp_vec;
+use crate::math::vector::Point;
+use crate::util::hittable::RayCollision;
+use crate::util::noise::Noise;
 
+// images and patterns are behind an Arc, so materials can be cloned onto
+// every shape without copying the pixels or noise tables.
 #[derive(Clone, Debug)]
 pub enum Texture {
     Solid(Color),
-    Img(DynamicImage)
+    Img(Arc<ImageTexture>),
+    Checker(Arc<Checker>),
+    Noise(Arc<Noise>),
+}
+
+// checkerboard alternating between two textures, squares size across.
+#[derive(Clone, Debug)]
+pub struct Checker {
+    pub even: Texture,
+    pub odd: Texture,
+    pub size: f32,
+    pub mapping: Mapping,
+}
+
+// what a checker's squares are laid out on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mapping {
+    Object, // cubes in 3d, so it carries on through curved shapes without stretching.
+    Uv, // squares in uv space, following the surface like an image would.
+}
+
+impl Mapping {
+    pub const NAMES: &'static str = "object, uv";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "object" => Some(Mapping::Object),
+            "uv" => Some(Mapping::Uv),
+            _ => None,
+        }
+    }
 }
 
 impl Texture {
@@ -13,50 +56,531 @@ impl Texture {
         Texture::Solid(c)
     }
 
-    pub fn from_img(file_name: String) -> Self {
-        let img = ImageReader::open(file_name.as_str());
+    // an image file, read and decoded on every call. TextureCache::load
+    // shares the pixels between everything that uses the same file.
+    pub fn load(path: &Path, options: ImageOptions) -> Result<Self, TextureError> {
+        let img = decode_file(path)?;
+        Ok(Texture::Img(Arc::new(ImageTexture::new(&img, options))))
+    }
 
-        // verify and decode image
-        if let Result::Ok(image_result) = img {
-            if let Result::Ok(data) = image_result.decode() {
-                return Texture::Img(data);
-            }
-        }
+    // loud magenta, so a texture that couldn't be loaded stands out in the
+    // render instead of passing for a dark material.
+    pub fn missing() -> Self {
+        Texture::Solid(Color::new(1.0, 0.0, 1.0))
+    }
 
-        // return black solid if image reader failed
-        Texture::Solid(Color::new(0.0, 0.0, 0.0))
+    // the texture where collision hit. solid patterns use the point on the
+    // shape as it was made, so they don't slide over moved or instanced ones.
+    pub fn at(&self, collision: &RayCollision) -> Color {
+        self.get(&collision.uv.a, &collision.uv.b, &collision.local_point, collision.footprint)
     }
 
-    pub fn get(&self, u: &f32, v: &f32) -> Color {
+    // image textures and uv checkers look up uv, procedural ones the point.
+    // footprint is how wide a pixel is at the lookup, in uv units, so
+    // trilinear filtering can pick a mip level. 0 for the sharpest.
+    pub fn get(&self, u: &f32, v: &f32, point: &Point, footprint: f32) -> Color {
         match self {
             Self::Solid(c) => c.to_owned(),
-            Self::Img(img) => {
-                let (i, j) = Texture::uv_to_ij(img, u, v);
+            Self::Checker(checker) => {
+                let cell = |x: f32| (x / checker.size).floor() as i64;
+                let parity = match checker.mapping {
+                    Mapping::Object => cell(point.a) + cell(point.b) + cell(point.c),
+                    Mapping::Uv => cell(*u) + cell(*v),
+                };
+
+                if parity.rem_euclid(2) == 0 {
+                    checker.even.get(u, v, point, footprint)
+                } else {
+                    checker.odd.get(u, v, point, footprint)
+                }
+            },
+            Self::Noise(noise) => noise.value(*point),
+            Self::Img(img) => img.get(*u, *v, footprint),
+        }
+    }
+}
+
+// why an image file couldn't be turned into a texture.
+#[derive(Debug)]
+pub enum TextureError {
+    Io { path: PathBuf, source: io::Error }, // missing, or couldn't be read.
+    Decode { path: PathBuf, source: ImageError }, // read, but broken.
+    Unsupported { path: PathBuf, source: ImageError }, // a format or color type the image crate can't load.
+}
+
+impl TextureError {
+    fn from_image(path: &Path, error: ImageError) -> Self {
+        let path = path.to_owned();
+        match error {
+            ImageError::IoError(source) => TextureError::Io { path, source },
+            ImageError::Unsupported(_) => TextureError::Unsupported { path, source: error },
+            _ => TextureError::Decode { path, source: error },
+        }
+    }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Io { path, source } => write!(f, "can't read image '{}': {}", path.display(), source),
+            TextureError::Decode { path, source } => write!(f, "can't decode image '{}': {}", path.display(), source),
+            TextureError::Unsupported { path, source } => write!(f, "unsupported image '{}': {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Io { source, .. } => Some(source),
+            TextureError::Decode { source, .. } | TextureError::Unsupported { source, .. } => Some(source),
+        }
+    }
+}
+
+// so loaders that return io::Result can pass it on with ?. a missing file
+// keeps its NotFound kind.
+impl From<TextureError> for io::Error {
+    fn from(e: TextureError) -> Self {
+        let kind = match &e {
+            TextureError::Io { source, .. } => source.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+fn decode_file(path: &Path) -> Result<DynamicImage, TextureError> {
+    let reader = ImageReader::open(path)
+        .map_err(|source| TextureError::Io { path: path.to_owned(), source })?;
+
+    // the contents win over the extension, a png saved as .jpg still loads.
+    let reader = reader.with_guessed_format()
+        .map_err(|source| TextureError::Io { path: path.to_owned(), source })?;
+
+    reader.decode().map_err(|e| TextureError::from_image(path, e))
+}
+
+// Decoded images by file, so a texture used by many materials (or every
+// material of a mesh) is read and mipmapped once. The pixels depend on
+// srgb and whether mips are needed; the rest of ImageOptions is applied at
+// lookup, so textures differing only in those still share.
+#[derive(Debug, Default)]
+pub struct TextureCache {
+    images: HashMap<(PathBuf, bool, bool), Arc<Vec<Level>>>,
+    failed: HashSet<PathBuf>, // files that couldn't be loaded, warned about once and not read again.
+    pub missing_fallback: bool, // warn and use Texture::missing instead of failing.
+}
+
+impl TextureCache {
+    pub fn new(missing_fallback: bool) -> Self {
+        TextureCache { images: HashMap::new(), failed: HashSet::new(), missing_fallback }
+    }
+
+    pub fn load(&mut self, path: &Path, options: ImageOptions) -> Result<Texture, TextureError> {
+        // the same file by a different route is still the same file.
+        let file = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let key = (file, options.srgb, options.filter == Filter::Trilinear);
+
+        if let Some(levels) = self.images.get(&key) {
+            return Ok(Texture::Img(Arc::new(ImageTexture { levels: Arc::clone(levels), options })));
+        }
+        if self.missing_fallback && self.failed.contains(&key.0) {
+            return Ok(Texture::missing());
+        }
+
+        match decode_file(path) {
+            Ok(img) => {
+                let texture = ImageTexture::new(&img, options);
+                self.images.insert(key, Arc::clone(&texture.levels));
+                Ok(Texture::Img(Arc::new(texture)))
+            },
+            Err(e) if self.missing_fallback => {
+                eprintln!("Warning: using the missing texture, {}", e);
+                self.failed.insert(key.0);
+                Ok(Texture::missing())
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// how texels are blended between.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    Nearest, // blocky, the closest texel.
+    Bilinear, // the four closest texels.
+    Trilinear, // bilinear on the two mip levels closest to the pixel's size.
+}
+
+impl Filter {
+    pub const NAMES: &'static str = "nearest, bilinear, trilinear";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" => Some(Filter::Nearest),
+            "bilinear" => Some(Filter::Bilinear),
+            "trilinear" => Some(Filter::Trilinear),
+            _ => None,
+        }
+    }
+}
+
+// what happens to uvs outside 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wrap {
+    Repeat, // tile.
+    Mirror, // tile, every other copy flipped so the seams match.
+    Clamp, // stretch the edge texels.
+}
+
+impl Wrap {
+    pub const NAMES: &'static str = "repeat, mirror, clamp";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "repeat" => Some(Wrap::Repeat),
+            "mirror" => Some(Wrap::Mirror),
+            "clamp" => Some(Wrap::Clamp),
+            _ => None,
+        }
+    }
+
+    // texel index i folded into 0..size.
+    fn apply(self, i: i64, size: i64) -> i64 {
+        match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Mirror => {
+                let m = i.rem_euclid(2 * size);
+                if m < size { m } else { 2 * size - 1 - m }
+            },
+            Wrap::Clamp => i.clamp(0, size - 1),
+        }
+    }
+}
+
+// how an image file is turned into a texture and looked up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageOptions {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub scale: (f32, f32), // uvs are scaled, then offset, before the lookup.
+    pub offset: (f32, f32),
+    pub srgb: bool, // 8 and 16 bit images hold srgb colors. off for data like roughness.
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions { filter: Filter::Bilinear, wrap: Wrap::Repeat, scale: (1.0, 1.0), offset: (0.0, 0.0), srgb: true }
+    }
+}
+
+// one level of the mip pyramid, linear colors, top row first.
+#[derive(Clone, Debug)]
+struct Level {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
 
-                let rgba = img.get_pixel(i, j);
+impl Level {
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> Color {
+        let x = wrap.apply(x, self.width as i64);
+        let y = wrap.apply(y, self.height as i64);
+        self.pixels[(y * self.width as i64 + x) as usize]
+    }
+
+    // half the size, each texel the average of the (up to) four under it.
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let sum = self.texel(2 * x, 2 * y, Wrap::Clamp)
+                    + self.texel(2 * x + 1, 2 * y, Wrap::Clamp)
+                    + self.texel(2 * x, 2 * y + 1, Wrap::Clamp)
+                    + self.texel(2 * x + 1, 2 * y + 1, Wrap::Clamp);
+                pixels.push(sum.scalar_mul(0.25));
+            }
+        }
+
+        Level { width, height, pixels }
+    }
+
+    // s and t in texels, texel centers on the halves.
+    fn bilinear(&self, s: f32, t: f32, wrap: Wrap) -> Color {
+        let (s, t) = (s - 0.5, t - 0.5);
+        let (x, y) = (s.floor(), t.floor());
+        let (fx, fy) = (s - x, t - y);
+        let (x, y) = (x as i64, y as i64);
+
+        let top = lerp_vec(self.texel(x, y, wrap), self.texel(x + 1, y, wrap), fx);
+        let bottom = lerp_vec(self.texel(x, y + 1, wrap), self.texel(x + 1, y + 1, wrap), fx);
+        lerp_vec(top, bottom, fy)
+    }
+
+    fn nearest(&self, s: f32, t: f32, wrap: Wrap) -> Color {
+        self.texel(s.floor() as i64, t.floor() as i64, wrap)
+    }
+}
+
+// An image decoded to linear float colors, with a mip pyramid (each level
+// half the size of the last) when filtered trilinearly. float images (hdr,
+// exr) are kept as is, so values above 1 survive.
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+    levels: Arc<Vec<Level>>,
+    pub options: ImageOptions,
+}
+
+impl ImageTexture {
+    pub fn new(img: &DynamicImage, options: ImageOptions) -> Self {
+        let is_float = matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+        let decode = options.srgb && !is_float;
+
+        let rgb = img.to_rgb32f();
+        let pixels = rgb
+            .pixels()
+            .map(|p| {
+                let c = Color::new(p[0], p[1], p[2]);
+                if decode { srgb_to_linear(c) } else { c }
+            })
+            .collect();
+
+        let mut levels = vec![Level { width: rgb.width().max(1), height: rgb.height().max(1), pixels }];
+
+        if options.filter == Filter::Trilinear {
+            while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+                let next = last.downsample();
+                levels.push(next);
+            }
+        }
+
+        ImageTexture { levels: Arc::new(levels), options }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.levels[0].height
+    }
 
-                Color { 
-                    a: rgba[0] as f32, 
-                    b: rgba[1] as f32, 
-                    c: rgba[2] as f32
-                }.scalar_div(255.0)
+    pub fn get(&self, u: f32, v: f32, footprint: f32) -> Color {
+        let options = &self.options;
+        let u = u * options.scale.0 + options.offset.0;
+        // v runs up, rows run down.
+        let v = 1.0 - (v * options.scale.1 + options.offset.1);
+
+        let at_level = |level: &Level, filter: Filter| {
+            let (s, t) = (u * level.width as f32, v * level.height as f32);
+            match filter {
+                Filter::Nearest => level.nearest(s, t, options.wrap),
+                _ => level.bilinear(s, t, options.wrap),
             }
+        };
+
+        match options.filter {
+            Filter::Nearest | Filter::Bilinear => at_level(&self.levels[0], options.filter),
+            Filter::Trilinear => {
+                // the level where a texel is about as wide as the pixel.
+                let scale = options.scale.0.abs().max(options.scale.1.abs());
+                let texels = footprint * scale * self.width().max(self.height()) as f32;
+                let lod = texels.max(1.0).log2().min((self.levels.len() - 1) as f32);
+
+                let fine = lod.floor() as usize;
+                let coarse = (fine + 1).min(self.levels.len() - 1);
+                let t = lod - fine as f32;
+
+                let color = at_level(&self.levels[fine], Filter::Bilinear);
+                if t > 0.0 {
+                    lerp_vec(color, at_level(&self.levels[coarse], Filter::Bilinear), t)
+                } else {
+                    color
+                }
+            },
         }
     }
+}
+
+// the srgb transfer curve undone, per channel.
+fn srgb_to_linear(c: Color) -> Color {
+    let decode = |x: f32| {
+        if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) }
+    };
+
+    Color::new(decode(c.a), decode(c.b), decode(c.c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::matrix::Matrix4;
+    use crate::math::ray::Ray;
+    use crate::shapes::instance::Instance;
+    use crate::shapes::motion::Motion;
+    use crate::shapes::shape::Shape;
+    use crate::shapes::sphere::Sphere;
+    use crate::util::hittable::Hittable;
+    use crate::util::material::Material;
+    use image::Rgb32FImage;
+
+    // 4x1, texels 0, 1, 2, 3 in every channel. float, so no srgb decoding.
+    fn ramp(options: ImageOptions) -> ImageTexture {
+        let pixels: Vec<f32> = (0..4).flat_map(|i| [i as f32; 3]).collect();
+        let img = DynamicImage::ImageRgb32F(Rgb32FImage::from_raw(4, 1, pixels).unwrap());
+        ImageTexture::new(&img, options)
+    }
+
+    fn nearest(wrap: Wrap) -> ImageOptions {
+        ImageOptions { filter: Filter::Nearest, wrap, ..ImageOptions::default() }
+    }
+
+    // the texel at u, in texel widths from the left edge.
+    fn at(texture: &ImageTexture, texels: f32) -> f32 {
+        texture.get(texels / 4.0, 0.5, 0.0).a
+    }
+
+    #[test]
+    fn wrap_modes_fold_indices() {
+        let folded = |wrap: Wrap| (-5..9).map(|i| wrap.apply(i, 4)).collect::<Vec<_>>();
+
+        assert_eq!(folded(Wrap::Repeat), vec![3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]);
+        assert_eq!(folded(Wrap::Mirror), vec![3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0, 0]);
+        assert_eq!(folded(Wrap::Clamp), vec![0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn wrap_modes_past_the_edges() {
+        let repeat = ramp(nearest(Wrap::Repeat));
+        assert_eq!(at(&repeat, 5.5), 1.0);
+        assert_eq!(at(&repeat, -0.5), 3.0);
+
+        let mirror = ramp(nearest(Wrap::Mirror));
+        assert_eq!(at(&mirror, 4.5), 3.0);
+        assert_eq!(at(&mirror, 6.5), 1.0);
+        assert_eq!(at(&mirror, -1.5), 1.0);
+
+        let clamp = ramp(nearest(Wrap::Clamp));
+        assert_eq!(at(&clamp, 9.5), 3.0);
+        assert_eq!(at(&clamp, -3.0), 0.0);
+    }
+
+    #[test]
+    fn bilinear_blends_neighbours() {
+        let clamp = ramp(ImageOptions { wrap: Wrap::Clamp, ..ImageOptions::default() });
+        assert!((at(&clamp, 1.5) - 1.0).abs() < 1e-5);
+        assert!((at(&clamp, 2.0) - 1.5).abs() < 1e-5);
+
+        // the seam blends the last texel into the first.
+        let repeat = ramp(ImageOptions::default());
+        assert!((at(&repeat, 4.0) - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn scale_and_offset_move_uvs() {
+        // twice across, then a quarter along: u = 0.3 looks up 0.85.
+        let options = ImageOptions { scale: (2.0, 1.0), offset: (0.25, 0.0), ..nearest(Wrap::Repeat) };
+        let texture = ramp(options);
+
+        assert_eq!(texture.get(0.3, 0.5, 0.0).a, 3.0);
+        assert_eq!(texture.get(0.45, 0.5, 0.0).a, 0.0);
+    }
+
+    #[test]
+    fn trilinear_fades_to_the_average() {
+        let texture = ramp(ImageOptions { filter: Filter::Trilinear, ..ImageOptions::default() });
+
+        // a pixel covering the whole image sees its average.
+        assert!((texture.get(0.1, 0.5, 1.0).a - 1.5).abs() < 1e-5);
+        // and a tiny one the texel itself.
+        assert_eq!(texture.get(0.125, 0.5, 0.0).a, 0.0);
+    }
+
+    #[test]
+    fn srgb_is_decoded_for_8_bit_images() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_raw(1, 1, vec![128, 128, 128]).unwrap());
+
+        let decoded = ImageTexture::new(&img, ImageOptions::default()).get(0.5, 0.5, 0.0).a;
+        assert!((decoded - 0.2158).abs() < 1e-3);
+
+        let raw = ImageTexture::new(&img, ImageOptions { srgb: false, ..ImageOptions::default() }).get(0.5, 0.5, 0.0).a;
+        assert!((raw - 128.0 / 255.0).abs() < 1e-5);
+    }
+
+    fn checker(mapping: Mapping, size: f32) -> Texture {
+        let even = Texture::Solid(Color::new(0.0, 0.0, 0.0));
+        let odd = Texture::Solid(Color::new(1.0, 1.0, 1.0));
+        Texture::Checker(Arc::new(Checker { even, odd, size, mapping }))
+    }
+
+    fn sphere_at(center: Point) -> Sphere {
+        Sphere::new_pos_t(center, Material::Lambertian(checker(Mapping::Object, 1.0)), 0.5)
+    }
+
+    #[test]
+    fn checkers_stick_to_placed_shapes() {
+        // the same spot on the sphere, seen straight and moved over by 1.
+        let plain = sphere_at(Point::new(0.25, 0.25, -3.0)).hit(Ray::new(Point::new(0.25, 0.25, 0.0), Point::new(0.0, 0.0, -1.0))).unwrap();
+
+        let object = Arc::new(Shape::sphere(sphere_at(Point::new(0.25, 0.25, -3.0))));
+        let instance = Instance::new(object, Matrix4::translate(Point::new(1.0, 0.0, 0.0))).unwrap();
+        let moved = instance.hit(Ray::new(Point::new(1.25, 0.25, 0.0), Point::new(0.0, 0.0, -1.0))).unwrap();
+
+        let texture = checker(Mapping::Object, 1.0);
+        assert!((moved.hit_point.a - 1.25).abs() < 1e-5);
+        assert_eq!(texture.at(&moved).a, texture.at(&plain).a);
+        // and a hit point in world space would have crossed into the next cube.
+        assert_ne!(texture.get(&0.0, &0.0, &moved.hit_point, 0.0).a, texture.at(&plain).a);
+    }
+
+    #[test]
+    fn checkers_stick_to_moving_spheres() {
+        let motion = Motion::linear(Point::new(0.25, 0.25, -3.0), Point::new(0.75, 0.25, -3.0));
+        let sphere = Sphere::moving(motion, Material::Lambertian(checker(Mapping::Object, 1.0)), 0.5);
+
+        let start = sphere.hit(Ray::new_at(Point::new(0.25, 0.25, 0.0), Point::new(0.0, 0.0, -1.0), 0.0)).unwrap();
+        let end = sphere.hit(Ray::new_at(Point::new(0.75, 0.25, 0.0), Point::new(0.0, 0.0, -1.0), 1.0)).unwrap();
+
+        assert!((end.local_point + start.local_point.scalar_mul(-1.0)).len() < 1e-5);
+    }
 
-    pub fn uv_to_ij(img: &DynamicImage, u: &f32, v: &f32) -> (u32, u32) {
-        let u_clamp = u.clamp(0.0, 1.0);
-        let v_clamp = 1.0 - v.clamp(0.0, 1.0);
+    #[test]
+    fn uv_checkers_follow_uvs() {
+        let texture = checker(Mapping::Uv, 0.25);
+        let at = |u: f32, v: f32| texture.get(&u, &v, &Point::origin(), 0.0).a;
 
-        let width = img.width();
-        let height = img.height();
+        assert_eq!(at(0.1, 0.1), 0.0);
+        assert_eq!(at(0.3, 0.1), 1.0);
+        assert_eq!(at(0.3, 0.3), 0.0);
+        // the point in space doesn't matter.
+        assert_eq!(texture.get(&0.1, &0.1, &Point::new(1.5, 0.0, 0.0), 0.0).a, 0.0);
+    }
+
+    #[test]
+    fn mappings_by_name() {
+        assert_eq!(Mapping::from_name("UV"), Some(Mapping::Uv));
+        assert_eq!(Mapping::from_name("object"), Some(Mapping::Object));
+        assert_eq!(Mapping::from_name("world"), None);
+    }
 
-        let mut i = (u_clamp * width as f32) as u32;
-        let mut j = (v_clamp * height as f32) as u32;
+    #[test]
+    fn failed_loads_are_cached() {
+        let dir = std::env::temp_dir().join(format!("rs-raycast-texture-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("late.png");
 
-        if i >= width { i = width - 1 }
-        if j >= height { j = height - 1 }
+        let mut textures = TextureCache::new(true);
+        assert!(matches!(textures.load(&path, ImageOptions::default()), Ok(Texture::Solid(_))));
 
-        (i, j)
+        // turning up afterwards doesn't matter, the file isn't looked at again.
+        image::RgbImage::new(1, 1).save(&path).unwrap();
+        assert!(matches!(textures.load(&path, ImageOptions::default()), Ok(Texture::Solid(_))));
+        assert!(matches!(TextureCache::new(true).load(&path, ImageOptions::default()), Ok(Texture::Img(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
     }
-}
\ No newline at end of file
+}